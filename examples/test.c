@@ -1,8 +1,12 @@
+int calls;
+
 int add(int x, int y) {
   int ans = x + y;
+  calls = calls + 1;
   return ans;
 }
 
+/* -1, 0 or 1 depending on the sign of x */
 int sign(int x) {
   if (x > 0) {
     return 1;
@@ -13,6 +17,17 @@ int sign(int x) {
   }
 }
 
+int sum_to(int n) {
+  int i = 0;
+  int sum = 0;
+  // 1 + 2 + ... + n
+  while (i < n) {
+    i = i + 1;
+    sum = sum + i;
+  }
+  return sum;
+}
+
 int main() {
   int a = 3;
   int b;
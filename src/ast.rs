@@ -1,49 +1,305 @@
-#[derive(Clone)]
+use chumsky::span::SimpleSpan;
+
+/// Byte range of a node in its source file.
+pub type Span = SimpleSpan;
+
+#[derive(Clone, Debug)]
 pub struct Ast {
-    pub program: Vec<Function>,
+    pub program: Vec<Item>,
+}
+
+/// A top-level item of a source file.
+#[derive(Clone, Debug)]
+pub enum Item {
+    Function(Function),
+    /// A file-scope variable.
+    Global(Declaration),
+    Struct(StructDef),
+    Enum(EnumDef),
+    Typedef(Typedef),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Function {
     pub name: String,
     pub params: Vec<Parameter>,
-    pub body: Block,
+    /// Whether the parameters end in `...`, which any number of further
+    /// arguments may be passed for.
+    pub variadic: bool,
+    /// `None` for a prototype, which declares a function defined elsewhere.
+    pub body: Option<Block>,
     pub ret_type: Type,
+    pub span: Span,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Parameter {
-    pub name: String,
+    /// Parameters of a prototype may be left unnamed.
+    pub name: Option<String>,
     pub param_type: Type,
+    pub span: Span,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Type {
+    /// `char`, which is signed, as on x86-64 and on Apple's AArch64
+    /// platforms; `signed char` is taken to be the same type. The driver
+    /// rejects targets where `char` is unsigned.
+    Char,
+    UnsignedChar,
     Int,
+    UnsignedInt,
+    Long,
+    UnsignedLong,
+    LongLong,
+    UnsignedLongLong,
     Float,
     Void,
+    Pointer(Box<Type>),
+    /// An array of a fixed number of elements.
+    Array(Box<Type>, u32),
+    /// A struct type, named by its tag. Code generation adds a number to the
+    /// tag of all but the first type given it, e.g. `tmp.1`, as the same tag
+    /// names different types in different scopes.
+    Struct(String),
+    /// A union type, named by its tag, numbered as that of a struct type.
+    Union(String),
+    /// An enumerated type, named by its tag, numbered as that of a struct
+    /// type. Its values are `int`s.
+    Enum(String),
+    /// A typedef name, which stands for the type it was declared with in
+    /// the scope it is used in. Resolved before lowering.
+    Typedef(String),
+}
+
+/// Integer types follow the LP64 model: `char` is 8 bits wide, `int` 32 bits,
+/// `long` and `long long` are 64 bits wide, as are pointers.
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        self.int_bits().is_some()
+    }
+
+    pub fn is_arithmetic(&self) -> bool {
+        self.is_integer() || *self == Type::Float
+    }
+
+    /// The type pointed to, if this is a pointer type.
+    pub fn pointee(&self) -> Option<&Type> {
+        match self {
+            Type::Pointer(pointee) => Some(pointee),
+            _ => None,
+        }
+    }
+
+    /// The element type, if this is an array type.
+    pub fn element(&self) -> Option<&Type> {
+        match self {
+            Type::Array(element, _) => Some(element),
+            _ => None,
+        }
+    }
+
+    pub fn is_struct_or_union(&self) -> bool {
+        matches!(self, Type::Struct(_) | Type::Union(_))
+    }
+
+    pub fn is_void_pointer(&self) -> bool {
+        self.pointee() == Some(&Type::Void)
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            Type::Char | Type::Int | Type::Long | Type::LongLong | Type::Enum(_)
+        )
+    }
+
+    /// Width of an integer type.
+    pub fn int_bits(&self) -> Option<u32> {
+        match self {
+            Type::Char | Type::UnsignedChar => Some(8),
+            Type::Int | Type::UnsignedInt | Type::Enum(_) => Some(32),
+            Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong => Some(64),
+            Type::Float
+            | Type::Void
+            | Type::Pointer(_)
+            | Type::Array(..)
+            | Type::Struct(_)
+            | Type::Union(_)
+            | Type::Typedef(_) => None,
+        }
+    }
+
+    /// Largest value of an integer type.
+    pub fn max_value(&self) -> u64 {
+        match (self.int_bits(), self.is_signed()) {
+            (Some(bits), true) => (1 << (bits - 1)) - 1,
+            (Some(bits), false) => u64::MAX >> (64 - bits),
+            (None, _) => 0,
+        }
+    }
+
+    /// Integer conversion rank (C11 6.3.1.1).
+    fn rank(&self) -> u8 {
+        match self {
+            Type::Char | Type::UnsignedChar => 1,
+            Type::Int | Type::UnsignedInt | Type::Enum(_) => 2,
+            Type::Long | Type::UnsignedLong => 3,
+            Type::LongLong | Type::UnsignedLongLong => 4,
+            Type::Float
+            | Type::Void
+            | Type::Pointer(_)
+            | Type::Array(..)
+            | Type::Struct(_)
+            | Type::Union(_)
+            | Type::Typedef(_) => 0,
+        }
+    }
+
+    fn to_unsigned(&self) -> Type {
+        match self {
+            Type::Char => Type::UnsignedChar,
+            Type::Int => Type::UnsignedInt,
+            Type::Long => Type::UnsignedLong,
+            Type::LongLong => Type::UnsignedLongLong,
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type a value of this type is converted to by the integer
+    /// promotions (C11 6.3.1.1): `char`s and enumerated types are `int`s
+    /// after them, as an `int` can represent all their values.
+    pub fn promoted(&self) -> Type {
+        match self {
+            Type::Char | Type::UnsignedChar | Type::Enum(_) => Type::Int,
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type both operands of an arithmetic operator are converted to,
+    /// by the usual arithmetic conversions (C11 6.3.1.8), after the integer
+    /// promotions. `None` if either operand is not arithmetic.
+    pub fn common_arithmetic(&self, other: &Type) -> Option<Type> {
+        match (&self.promoted(), &other.promoted()) {
+            (a, b) if !a.is_arithmetic() || !b.is_arithmetic() => None,
+            (Type::Float, _) | (_, Type::Float) => Some(Type::Float),
+            (a, b) if a == b => Some(a.clone()),
+            (a, b) if a.is_signed() == b.is_signed() => {
+                Some(if a.rank() >= b.rank() { a } else { b }.clone())
+            }
+            (a, b) => {
+                let (signed, unsigned) = if a.is_signed() { (a, b) } else { (b, a) };
+                if unsigned.rank() >= signed.rank() {
+                    Some(unsigned.clone())
+                } else if signed.max_value() >= unsigned.max_value() {
+                    Some(signed.clone())
+                } else {
+                    Some(signed.to_unsigned())
+                }
+            }
+        }
+    }
+}
+
+/// The definition of a struct type, e.g. `struct point { int x; float y; }`,
+/// or of a union type, whose members all start at the same address.
+#[derive(Clone, Debug)]
+pub struct StructDef {
+    pub kind: StructKind,
+    pub tag: String,
+    pub members: Vec<Member>,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StructKind {
+    Struct,
+    Union,
+}
+
+impl StructKind {
+    /// The keyword introducing a type of this kind.
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            StructKind::Struct => "struct",
+            StructKind::Union => "union",
+        }
+    }
+
+    /// The type named by `tag` for this kind.
+    pub fn named(&self, tag: String) -> Type {
+        match self {
+            StructKind::Struct => Type::Struct(tag),
+            StructKind::Union => Type::Union(tag),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Member {
+    pub name: String,
+    pub member_type: Type,
+    pub span: Span,
+}
+
+/// The definition of an enumerated type, e.g. `enum color { RED, GREEN = 5 }`.
+/// The tag may be left out, in which case only the enumerators are declared.
+#[derive(Clone, Debug)]
+pub struct EnumDef {
+    pub tag: Option<String>,
+    pub enumerators: Vec<Enumerator>,
+    pub span: Span,
+}
+
+/// An enumeration constant. Without an explicit value it is one more than
+/// the previous one, or zero if it is the first.
+#[derive(Clone, Debug)]
+pub struct Enumerator {
+    pub name: String,
+    pub value: Option<Expression>,
+    pub span: Span,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Block {
     pub items: Vec<BlockItem>,
+    pub span: Span,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum BlockItem {
     Decl(Declaration),
+    Struct(StructDef),
+    Enum(EnumDef),
+    Typedef(Typedef),
     Stmt(Statement),
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Declaration {
     pub var_type: Type,
     pub name: String,
     pub init: Option<Expression>,
+    pub span: Span,
+}
+
+/// A typedef declaration, e.g. `typedef unsigned long size_t;`, which makes
+/// `name` stand for `ty`.
+#[derive(Clone, Debug)]
+pub struct Typedef {
+    pub name: String,
+    pub ty: Type,
+    pub span: Span,
 }
 
-#[derive(Clone)]
-pub enum Statement {
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum StatementKind {
     Block(Block),
     Expr(Expression),
     Ret(Option<Expression>),
@@ -56,11 +312,34 @@ pub enum Statement {
         condition: Expression,
         body: Box<Statement>,
     },
+    /// Jumps to the `case` label in `body` whose value is that of
+    /// `condition`, or else to its `default` label, if any.
+    Switch {
+        condition: Expression,
+        body: Box<Statement>,
+    },
+    /// A statement labelled `case value:` in the body of a `switch`.
+    Case {
+        value: Expression,
+        body: Box<Statement>,
+    },
+    /// A statement labelled `default:` in the body of a `switch`.
+    Default(Box<Statement>),
+    /// Leaves the innermost loop or `switch`.
+    Break,
     Empty,
+    /// Placeholder for a statement that failed to parse.
+    Error,
 }
 
-#[derive(Clone)]
-pub enum Expression {
+#[derive(Clone, Debug)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Clone, Debug)]
+pub enum ExpressionKind {
     Binary {
         left: Box<Expression>,
         operator: BinaryOperator,
@@ -80,11 +359,77 @@ pub enum Expression {
         callee: Box<Expression>,
         arguments: Vec<Expression>,
     },
+    /// `array[index]`, which is `*(array + index)`.
+    Subscript {
+        array: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// `object.member`; `pointer->member` is parsed as `(*pointer).member`.
+    Member {
+        object: Box<Expression>,
+        member: String,
+    },
+    /// `(ty) operand`, an explicit conversion.
+    Cast {
+        ty: Type,
+        operand: Box<Expression>,
+    },
+    /// Placeholder for an expression that failed to parse.
+    Error,
+}
+
+/// An expression designating an object, which may be assigned to.
+#[derive(Clone, Debug)]
+pub struct LValue {
+    pub kind: LValueKind,
+    pub span: Span,
 }
 
-#[derive(Clone)]
-pub enum LValue {
+#[derive(Clone, Debug)]
+pub enum LValueKind {
     Var(String),
+    /// `*p`, the object a pointer points to.
+    Deref(Box<Expression>),
+    /// `a[i]`, an element of an array.
+    Subscript {
+        array: Box<Expression>,
+        index: Box<Expression>,
+    },
+    /// `s.x`, a member of a struct or union.
+    Member {
+        object: Box<Expression>,
+        member: String,
+    },
+}
+
+impl LValue {
+    /// Classifies `expr` as an lvalue, handing it back unchanged when it does
+    /// not designate an object.
+    pub fn classify(expr: Expression) -> Result<LValue, Expression> {
+        let span = expr.span;
+        match expr.kind {
+            ExpressionKind::Variable(name) => Ok(LValue {
+                kind: LValueKind::Var(name),
+                span,
+            }),
+            ExpressionKind::Unary {
+                operator: UnaryOperator::Deref,
+                operand,
+            } => Ok(LValue {
+                kind: LValueKind::Deref(operand),
+                span,
+            }),
+            ExpressionKind::Subscript { array, index } => Ok(LValue {
+                kind: LValueKind::Subscript { array, index },
+                span,
+            }),
+            ExpressionKind::Member { object, member } => Ok(LValue {
+                kind: LValueKind::Member { object, member },
+                span,
+            }),
+            kind => Err(Expression { kind, span }),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -104,15 +449,39 @@ pub enum BinaryOperator {
     Or,
 }
 
+impl BinaryOperator {
+    /// Whether this is an equality or relational operator, whose operands
+    /// are compared rather than combined.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOperator::Equal
+                | BinaryOperator::NotEqual
+                | BinaryOperator::Less
+                | BinaryOperator::LessEqual
+                | BinaryOperator::Greater
+                | BinaryOperator::GreaterEqual
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum UnaryOperator {
     Plus,
     Negate,
     Not,
+    /// `*`, indirection through a pointer.
+    Deref,
+    /// `&`, the address of an lvalue.
+    AddressOf,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum Literal {
-    Int(i32),
+    /// An integer constant, already typed by its value, base and suffix.
+    Int {
+        value: u64,
+        ty: Type,
+    },
     Float(f32),
 }
@@ -0,0 +1,534 @@
+use inkwell::{
+    AddressSpace,
+    attributes::{Attribute, AttributeLoc},
+    types::{AnyType, BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType},
+    values::{BasicMetadataValueEnum, BasicValueEnum, PointerValue},
+};
+
+use super::{CodeGen, FunctionEntry, Result, into_llvm_type::IntoLlvmType};
+use crate::ast::Type;
+
+/// The C calling convention of the target, as far as passing structs and
+/// unions by value goes; scalars are passed as themselves everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Abi {
+    /// System V AMD64, used by Linux, the BSDs and macOS.
+    SysV,
+    /// The AArch64 procedure call standard, including Apple's variant.
+    Aapcs64,
+    /// Any other target. Structs are passed as LLVM aggregates, which only
+    /// code compiled by tinycc agrees on.
+    Other,
+}
+
+impl Abi {
+    pub(super) fn for_triple(triple: &str) -> Abi {
+        let arch = triple.split('-').next().unwrap_or_default();
+        match arch {
+            // Windows is LLP64, which `Type` and struct layout do not follow
+            _ if triple.contains("windows") => Abi::Other,
+            "x86_64" | "amd64" => Abi::SysV,
+            "aarch64" | "arm64" | "arm64e" => Abi::Aapcs64,
+            _ => Abi::Other,
+        }
+    }
+}
+
+/// How a parameter or the return value crosses a call.
+#[derive(Clone, Debug)]
+pub(super) enum PassMode<'ctx> {
+    /// As its own LLVM type.
+    Direct,
+    /// Reinterpreted as `parts`, which are passed as separate arguments, or
+    /// returned as a struct of them.
+    Cast(Vec<BasicTypeEnum<'ctx>>),
+    /// In memory, through a pointer to a copy made by the caller. A `byval`
+    /// argument is copied onto the stack by LLVM; a value returned in memory
+    /// is written through an `sret` pointer passed as the first argument.
+    Indirect { byval: bool },
+}
+
+/// How the parameters and the return value of a function are passed.
+#[derive(Clone, Debug)]
+pub(super) struct Signature<'ctx> {
+    pub(super) ret: PassMode<'ctx>,
+    pub(super) params: Vec<PassMode<'ctx>>,
+}
+
+/// Argument registers left while System V parameters are assigned to them.
+/// A struct goes to memory as a whole when its parts do not all fit.
+struct Registers {
+    int: usize,
+    sse: usize,
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    /// The LLVM type of a function with the given C signature, and how its
+    /// structs are passed in it. The arguments for the `...` of a variadic
+    /// function are passed as LLVM varargs.
+    pub(super) fn lower_signature(
+        &self,
+        ret: &Type,
+        params: &[Type],
+        variadic: bool,
+    ) -> (FunctionType<'ctx>, Signature<'ctx>) {
+        let ret_mode = match ret {
+            ty if ty.is_struct_or_union() => self.classify(ret, true),
+            _ => PassMode::Direct,
+        };
+        let mut registers = Registers { int: 6, sse: 8 };
+        if let PassMode::Indirect { .. } = ret_mode {
+            registers.int -= 1;
+        }
+        let param_modes: Vec<_> = params
+            .iter()
+            .map(|ty| self.classify_param(ty, &mut registers))
+            .collect();
+
+        let ptr_type = self.context.ptr_type(AddressSpace::default());
+        let mut llvm_params: Vec<BasicMetadataTypeEnum> = Vec::new();
+        if let PassMode::Indirect { .. } = ret_mode {
+            llvm_params.push(ptr_type.into());
+        }
+        for (ty, mode) in params.iter().zip(&param_modes) {
+            match mode {
+                PassMode::Direct => llvm_params.push(ty.into_llvm_type(self).into()),
+                PassMode::Cast(parts) => llvm_params.extend(parts.iter().map(|&part| part.into())),
+                PassMode::Indirect { .. } => llvm_params.push(ptr_type.into()),
+            }
+        }
+        let fn_type = match (&ret_mode, ret) {
+            (_, Type::Void) | (PassMode::Indirect { .. }, _) => {
+                self.context.void_type().fn_type(&llvm_params, variadic)
+            }
+            (PassMode::Cast(parts), _) => self.cast_type(parts).fn_type(&llvm_params, variadic),
+            (PassMode::Direct, ret) => ret.into_llvm_type(self).fn_type(&llvm_params, variadic),
+        };
+        let signature = Signature {
+            ret: ret_mode,
+            params: param_modes,
+        };
+        (fn_type, signature)
+    }
+
+    fn classify_param(&self, ty: &Type, registers: &mut Registers) -> PassMode<'ctx> {
+        if self.abi != Abi::SysV {
+            return match ty {
+                ty if ty.is_struct_or_union() => self.classify(ty, false),
+                _ => PassMode::Direct,
+            };
+        }
+        let (int, sse) = match ty {
+            ty if ty.is_struct_or_union() => match self.classify(ty, false) {
+                PassMode::Cast(parts) => {
+                    let sse = parts.iter().filter(|part| !part.is_int_type()).count();
+                    let int = parts.len() - sse;
+                    if int > registers.int || sse > registers.sse {
+                        return PassMode::Indirect { byval: true };
+                    }
+                    registers.int -= int;
+                    registers.sse -= sse;
+                    return PassMode::Cast(parts);
+                }
+                mode => return mode,
+            },
+            Type::Float => (0, 1),
+            _ => (1, 0),
+        };
+        registers.int = registers.int.saturating_sub(int);
+        registers.sse = registers.sse.saturating_sub(sse);
+        PassMode::Direct
+    }
+
+    /// How a struct or union of type `ty` is passed as an argument, or
+    /// returned.
+    fn classify(&self, ty: &Type, ret: bool) -> PassMode<'ctx> {
+        let (size, _) = self.layout(ty);
+        let int = |bytes: u64| -> BasicTypeEnum<'ctx> {
+            self.context.custom_width_int_type(bytes as u32 * 8).into()
+        };
+        match self.abi {
+            Abi::SysV => match self.sysv_parts(ty, size) {
+                Some(parts) => PassMode::Cast(parts),
+                None => PassMode::Indirect { byval: !ret },
+            },
+            Abi::Aapcs64 => {
+                let mut scalars = Vec::new();
+                if size <= 16 {
+                    self.scalars(ty, 0, &mut scalars);
+                }
+                let float = self.context.f32_type();
+                // the members of a union overlap, so the floats are counted
+                // by size
+                let floats = size / 4;
+                match size {
+                    // a homogeneous floating-point aggregate goes to
+                    // consecutive floating-point registers
+                    _ if !scalars.is_empty()
+                        && floats <= 4
+                        && scalars.iter().all(|(_, ty)| *ty == Type::Float) =>
+                    {
+                        if ret && matches!(ty, Type::Struct(_)) {
+                            PassMode::Direct
+                        } else {
+                            PassMode::Cast(vec![float.array_type(floats as u32).into()])
+                        }
+                    }
+                    0..=8 if ret => PassMode::Cast(vec![int(size)]),
+                    0..=8 => PassMode::Cast(vec![int(8)]),
+                    9..=16 if ret => PassMode::Cast(vec![int(8), int(8)]),
+                    9..=16 => PassMode::Cast(vec![int(8).array_type(2).into()]),
+                    _ => PassMode::Indirect { byval: false },
+                }
+            }
+            Abi::Other => PassMode::Direct,
+        }
+    }
+
+    /// The eightbytes a System V struct or union is passed in: a `float` or
+    /// two in an SSE register when they are all the eightbyte holds, else an
+    /// integer register. `None` if it is passed in memory.
+    fn sysv_parts(&self, ty: &Type, size: u64) -> Option<Vec<BasicTypeEnum<'ctx>>> {
+        if size > 16 {
+            return None;
+        }
+        let mut scalars = Vec::new();
+        self.scalars(ty, 0, &mut scalars);
+        let parts = (0..size.div_ceil(8))
+            .map(|eightbyte| {
+                let start = eightbyte * 8;
+                let members: Vec<_> = scalars
+                    .iter()
+                    .filter(|(offset, _)| (start..start + 8).contains(offset))
+                    .collect();
+                let float = self.context.f32_type();
+                if members.iter().all(|(_, ty)| *ty == Type::Float) {
+                    if members.iter().any(|(offset, _)| *offset == start + 4) {
+                        float.vec_type(2).into()
+                    } else {
+                        float.into()
+                    }
+                } else {
+                    let bytes = (size - start).min(8);
+                    self.context.custom_width_int_type(bytes as u32 * 8).into()
+                }
+            })
+            .collect();
+        Some(parts)
+    }
+
+    /// The type a struct cast to `parts` is held in: the only part, or a
+    /// struct of all of them. It is at least as large and as aligned as the
+    /// struct itself.
+    fn cast_type(&self, parts: &[BasicTypeEnum<'ctx>]) -> BasicTypeEnum<'ctx> {
+        match parts {
+            [part] => *part,
+            _ => self.context.struct_type(parts, false).into(),
+        }
+    }
+
+    /// Reinterprets the struct `value` as `cast`, through memory.
+    fn build_cast(
+        &self,
+        value: BasicValueEnum<'ctx>,
+        cast: BasicTypeEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>> {
+        let slot = self.build_entry_alloca(cast, "abi.cast")?;
+        self.builder.build_store(slot, value)?;
+        Ok(self.builder.build_load(cast, slot, "abi.cast")?)
+    }
+
+    /// The attributes a call must carry, and which parameter, or the return
+    /// value, each is for.
+    fn abi_attributes(
+        &self,
+        signature: &Signature<'ctx>,
+        ret: &Type,
+        params: &[Type],
+    ) -> Vec<(AttributeLoc, Attribute)> {
+        let type_attribute = |name: &str, ty: &Type| {
+            self.context.create_type_attribute(
+                Attribute::get_named_enum_kind_id(name),
+                ty.into_llvm_type(self).as_any_type_enum(),
+            )
+        };
+        // a `char` is extended to 32 bits by whoever passes it, as C
+        // compilers for all supported targets expect
+        let extension = |ty: &Type| {
+            let name = match (ty.int_bits(), ty.is_signed()) {
+                (Some(8), true) => "signext",
+                (Some(8), false) => "zeroext",
+                _ => return None,
+            };
+            Some(
+                self.context
+                    .create_enum_attribute(Attribute::get_named_enum_kind_id(name), 0),
+            )
+        };
+        let mut attributes = Vec::new();
+        let mut index = 0;
+        match signature.ret {
+            PassMode::Indirect { .. } => {
+                attributes.push((AttributeLoc::Param(0), type_attribute("sret", ret)));
+                index = 1;
+            }
+            PassMode::Direct => {
+                attributes.extend(extension(ret).map(|ext| (AttributeLoc::Return, ext)));
+            }
+            PassMode::Cast(_) => {}
+        }
+        for (ty, mode) in params.iter().zip(&signature.params) {
+            let param = AttributeLoc::Param(index);
+            match mode {
+                PassMode::Cast(parts) => index += parts.len() as u32,
+                PassMode::Indirect { byval: true } => {
+                    let (_, align) = self.layout(ty);
+                    let align = self.context.create_enum_attribute(
+                        Attribute::get_named_enum_kind_id("align"),
+                        align.max(8),
+                    );
+                    attributes.push((param, type_attribute("byval", ty)));
+                    attributes.push((param, align));
+                    index += 1;
+                }
+                PassMode::Direct => {
+                    attributes.extend(extension(ty).map(|ext| (param, ext)));
+                    index += 1;
+                }
+                PassMode::Indirect { byval: false } => index += 1,
+            }
+        }
+        attributes
+    }
+
+    pub(super) fn add_abi_attributes(&self, function: &FunctionEntry<'ctx>) {
+        let attributes = self.abi_attributes(
+            &function.signature,
+            &function.ret_type,
+            &function.param_types,
+        );
+        for (location, attribute) in attributes {
+            function.value.add_attribute(location, attribute);
+        }
+    }
+
+    /// Calls `function` with `args`, already converted to its parameter
+    /// types, or promoted if they are for its `...`, returning `None` if it
+    /// returns `void`.
+    pub(super) fn build_abi_call(
+        &self,
+        function: &FunctionEntry<'ctx>,
+        args: Vec<BasicValueEnum<'ctx>>,
+    ) -> Result<Option<BasicValueEnum<'ctx>>> {
+        let signature = &function.signature;
+        let mut llvm_args: Vec<BasicMetadataValueEnum> = Vec::new();
+        let ret_slot = match signature.ret {
+            PassMode::Indirect { .. } => {
+                let slot =
+                    self.build_entry_alloca(function.ret_type.into_llvm_type(self), "call.ret")?;
+                llvm_args.push(slot.into());
+                Some(slot)
+            }
+            _ => None,
+        };
+        let mut args = args.into_iter();
+        for ((value, ty), mode) in args
+            .by_ref()
+            .zip(&function.param_types)
+            .zip(&signature.params)
+        {
+            match mode {
+                PassMode::Direct => llvm_args.push(value.into()),
+                PassMode::Cast(parts) => {
+                    let cast = self.build_cast(value, self.cast_type(parts))?;
+                    if parts.len() == 1 {
+                        llvm_args.push(cast.into());
+                    } else {
+                        for i in 0..parts.len() as u32 {
+                            let part = self.builder.build_extract_value(
+                                cast.into_struct_value(),
+                                i,
+                                "abi.part",
+                            )?;
+                            llvm_args.push(part.into());
+                        }
+                    }
+                }
+                PassMode::Indirect { .. } => {
+                    let copy = self.build_entry_alloca(ty.into_llvm_type(self), "arg.copy")?;
+                    self.builder.build_store(copy, value)?;
+                    llvm_args.push(copy.into());
+                }
+            }
+        }
+        // LLVM lowers the variadic arguments, all scalars, by the target's
+        // rules for them
+        llvm_args.extend(args.map(BasicMetadataValueEnum::from));
+
+        let call = self
+            .builder
+            .build_call(function.value, &llvm_args, "call")?;
+        for (location, attribute) in
+            self.abi_attributes(signature, &function.ret_type, &function.param_types)
+        {
+            call.add_attribute(location, attribute);
+        }
+        match (&signature.ret, ret_slot) {
+            (PassMode::Indirect { .. }, Some(slot)) => Ok(Some(self.builder.build_load(
+                function.ret_type.into_llvm_type(self),
+                slot,
+                "call.ret",
+            )?)),
+            (PassMode::Cast(_), _) => {
+                let value = call.try_as_basic_value().left().expect("cast return");
+                let slot = self.build_entry_alloca(value.get_type(), "abi.cast")?;
+                self.builder.build_store(slot, value)?;
+                Ok(Some(self.builder.build_load(
+                    function.ret_type.into_llvm_type(self),
+                    slot,
+                    "call.ret",
+                )?))
+            }
+            _ => Ok(call.try_as_basic_value().left()),
+        }
+    }
+
+    /// Stores the parameters of the function being lowered to memory,
+    /// returning where each one is held. Parameters passed in memory are
+    /// used in place.
+    pub(super) fn build_params(&self, names: &[&str]) -> Result<Vec<PointerValue<'ctx>>> {
+        let function = self.current_function();
+        let mut values = function.value.get_param_iter();
+        if let PassMode::Indirect { .. } = function.signature.ret {
+            values.next();
+        }
+        let mut slots = Vec::with_capacity(names.len());
+        for ((ty, mode), name) in function
+            .param_types
+            .iter()
+            .zip(&function.signature.params)
+            .zip(names)
+        {
+            let mut next = || values.next().expect("parameter of the LLVM function");
+            let slot = match mode {
+                PassMode::Direct => {
+                    let slot = self.build_entry_alloca(ty.into_llvm_type(self), name)?;
+                    self.builder.build_store(slot, next())?;
+                    slot
+                }
+                PassMode::Cast(parts) => {
+                    let cast = self.cast_type(parts);
+                    let slot = self.build_entry_alloca(cast, name)?;
+                    if parts.len() == 1 {
+                        self.builder.build_store(slot, next())?;
+                    } else {
+                        for i in 0..parts.len() as u32 {
+                            let part = self.builder.build_struct_gep(cast, slot, i, "abi.part")?;
+                            self.builder.build_store(part, next())?;
+                        }
+                    }
+                    slot
+                }
+                PassMode::Indirect { .. } => next().into_pointer_value(),
+            };
+            slots.push(slot);
+        }
+        Ok(slots)
+    }
+
+    /// Returns `value`, already converted to the return type, from the
+    /// function being lowered.
+    pub(super) fn build_return_value(&self, value: Option<BasicValueEnum<'ctx>>) -> Result<()> {
+        let function = self.current_function();
+        match (&function.signature.ret, value) {
+            (_, None) => self.builder.build_return(None)?,
+            (PassMode::Direct, Some(value)) => self.builder.build_return(Some(&value))?,
+            (PassMode::Cast(parts), Some(value)) => {
+                let cast = self.build_cast(value, self.cast_type(parts))?;
+                self.builder.build_return(Some(&cast))?
+            }
+            (PassMode::Indirect { .. }, Some(value)) => {
+                let sret = function
+                    .value
+                    .get_nth_param(0)
+                    .expect("sret parameter")
+                    .into_pointer_value();
+                self.builder.build_store(sret, value)?;
+                self.builder.build_return(None)?
+            }
+        };
+        Ok(())
+    }
+}
+
+#[test]
+fn test_struct_signatures() {
+    use super::CodeGenTrait;
+    use crate::parser::parse;
+    use inkwell::{context::Context, targets::TargetTriple};
+
+    let input = r#"
+        struct small { int x; float y; };
+        struct pair { float a; float b; };
+        struct mixed { long a; float b; };
+        struct big { long a; long b; long c; };
+        union word { int i; float f; };
+        union floats { float a; float b[2]; };
+
+        struct small f_small(struct small s);
+        struct pair f_pair(struct pair s);
+        struct mixed f_mixed(struct mixed s);
+        struct big f_big(struct big s);
+        union word f_word(union word w);
+        union floats f_floats(union floats f);
+    "#;
+    let cases = [
+        (
+            "x86_64-unknown-linux-gnu",
+            [
+                "i64 (i64)",
+                "<2 x float> (<2 x float>)",
+                "{ i64, float } (i64, float)",
+                "void (ptr, ptr)",
+                "i32 (i32)",
+                "<2 x float> (<2 x float>)",
+            ],
+        ),
+        (
+            "aarch64-apple-darwin",
+            [
+                "i64 (i64)",
+                "%struct.pair ([2 x float])",
+                "{ i64, i64 } ([2 x i64])",
+                "void (ptr, ptr)",
+                "i32 (i64)",
+                "[2 x float] ([2 x float])",
+            ],
+        ),
+    ];
+    for (triple, expected) in cases {
+        let ast = parse(input).unwrap();
+        let context = Context::create();
+        let codegen = CodeGen::new_for_target(&context, "my_module", &TargetTriple::create(triple));
+        ast.codegen(&codegen).unwrap();
+
+        let names = [
+            "f_small", "f_pair", "f_mixed", "f_big", "f_word", "f_floats",
+        ];
+        for (name, expected) in names.iter().zip(expected) {
+            let function = codegen.module.get_function(name).unwrap();
+            let ty = function.get_type().print_to_string();
+            assert_eq!(ty.to_str().unwrap(), expected, "{} on {}", name, triple);
+        }
+        let big = codegen.module.get_function("f_big").unwrap();
+        let big = big.print_to_string().to_string();
+        assert!(big.contains("sret(%struct.big)"), "{} on {}", big, triple);
+        assert_eq!(
+            big.contains("byval(%struct.big)"),
+            triple.starts_with("x86_64-unknown"),
+            "{} on {}",
+            big,
+            triple
+        );
+    }
+}
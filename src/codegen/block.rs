@@ -0,0 +1,358 @@
+use inkwell::basic_block::BasicBlock;
+
+use super::{
+    CodeGen, CodeGenError, CodeGenTrait, Result, Switch, Variable,
+    constant::{convert, has_integer_operands, int_value},
+    into_llvm_type::IntoLlvmType,
+};
+use crate::ast::{
+    Block, BlockItem, Declaration, Expression, Literal, Span, Statement, StatementKind, Type,
+};
+
+impl<'ctx> CodeGenTrait<'ctx> for Block {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        code_gen.push_scope();
+        let result = codegen_items(&self.items, code_gen);
+        code_gen.pop_scope();
+        result
+    }
+}
+
+/// Lowers block items into the current scope. Items following a terminator
+/// (e.g. code after `return`) are unreachable up to the next `case` or
+/// `default` label, but are still lowered, into a block nothing branches to,
+/// so that their errors are reported, their declarations are in scope for the
+/// labelled statements that follow, and labels nested in them are registered.
+pub(super) fn codegen_items(items: &[BlockItem], code_gen: &CodeGen<'_>) -> Result<()> {
+    for item in items {
+        let labelled = matches!(
+            item,
+            BlockItem::Stmt(Statement {
+                kind: StatementKind::Case { .. } | StatementKind::Default(_),
+                ..
+            })
+        );
+        if labelled || !code_gen.current_block_terminated() {
+            item.codegen(code_gen)?;
+            continue;
+        }
+        let function = code_gen.current_function().value;
+        let dead_bb = code_gen.context.append_basic_block(function, "dead");
+        code_gen.builder.position_at_end(dead_bb);
+        let labels = code_gen.switch_labels();
+        item.codegen(code_gen)?;
+        // unless a label nested in it was reached, the item cannot fall
+        // through to what follows
+        if code_gen.switch_labels() == labels && !code_gen.current_block_terminated() {
+            code_gen.builder.build_unreachable()?;
+        }
+    }
+    Ok(())
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for BlockItem {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match self {
+            BlockItem::Decl(decl) => decl.codegen(code_gen),
+            BlockItem::Struct(definition) => code_gen.define_struct(definition),
+            BlockItem::Enum(definition) => code_gen.define_enum(definition),
+            BlockItem::Typedef(typedef) => code_gen.define_typedef(typedef),
+            BlockItem::Stmt(stmt) => stmt.codegen(code_gen),
+        }
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for Declaration {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        let ty = code_gen
+            .resolve_type(&self.var_type)
+            .map_err(|e| e.or_span(self.span))?;
+        code_gen
+            .check_variable_type(&self.name, &ty)
+            .map_err(|e| e.or_span(self.span))?;
+        let ptr = code_gen.build_entry_alloca(ty.into_llvm_type(code_gen), &self.name)?;
+        if let Some(init) = &self.init {
+            if ty.element().is_some() {
+                return Err(CodeGenError::at(
+                    init.span,
+                    "array initializer must be an initializer list",
+                ));
+            }
+            let value = code_gen.build_coerced(init, &ty)?;
+            code_gen.builder.build_store(ptr, value)?;
+        }
+        code_gen
+            .declare_var(&self.name, Variable { ptr, ty })
+            .map_err(|e| e.or_span(self.span))
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for Statement {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match &self.kind {
+            // a `default` label has no location of its own but that of the
+            // statement it labels
+            StatementKind::Default(body) => {
+                let label = code_gen
+                    .add_default(self.span)
+                    .map_err(|e| e.or_span(self.span))?;
+                code_gen.start_labelled_block(label)?;
+                body.codegen(code_gen)
+            }
+            kind => kind.codegen(code_gen).map_err(|e| e.or_span(self.span)),
+        }
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for StatementKind {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match self {
+            StatementKind::Block(block) => block.codegen(code_gen),
+            StatementKind::Expr(expr) => code_gen.build_discarded(expr),
+            StatementKind::Ret(expr) => {
+                let ret_type = code_gen.current_function().ret_type;
+                match (expr, ret_type) {
+                    (None, Type::Void) => code_gen.build_return_value(None)?,
+                    (None, _) => {
+                        return Err(CodeGenError::new("non-void function should return a value"));
+                    }
+                    (Some(_), Type::Void) => {
+                        return Err(CodeGenError::new("void function should not return a value"));
+                    }
+                    (Some(expr), ret_type) => {
+                        let value = code_gen.build_coerced(expr, &ret_type)?;
+                        code_gen.build_return_value(Some(value))?;
+                    }
+                }
+                Ok(())
+            }
+            StatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let function = code_gen.current_function().value;
+                let condition = code_gen.build_condition_of(condition)?;
+
+                let then_bb = code_gen.context.append_basic_block(function, "if.then");
+                let else_bb = code_gen.context.append_basic_block(function, "if.else");
+                code_gen
+                    .builder
+                    .build_conditional_branch(condition, then_bb, else_bb)?;
+
+                // blocks that fall through to the join point; the join block
+                // is only created if at least one path reaches it
+                let mut fallthrough = Vec::new();
+
+                code_gen.builder.position_at_end(then_bb);
+                then_branch.codegen(code_gen)?;
+                if !code_gen.current_block_terminated() {
+                    fallthrough.extend(code_gen.builder.get_insert_block());
+                }
+
+                code_gen.builder.position_at_end(else_bb);
+                if let Some(else_branch) = else_branch {
+                    else_branch.codegen(code_gen)?;
+                }
+                if !code_gen.current_block_terminated() {
+                    fallthrough.extend(code_gen.builder.get_insert_block());
+                }
+
+                if !fallthrough.is_empty() {
+                    let merge_bb = code_gen.context.append_basic_block(function, "if.end");
+                    for bb in fallthrough {
+                        code_gen.builder.position_at_end(bb);
+                        code_gen.builder.build_unconditional_branch(merge_bb)?;
+                    }
+                    code_gen.builder.position_at_end(merge_bb);
+                }
+                Ok(())
+            }
+            StatementKind::While { condition, body } => {
+                let function = code_gen.current_function().value;
+                let cond_bb = code_gen.context.append_basic_block(function, "while.cond");
+                let body_bb = code_gen.context.append_basic_block(function, "while.body");
+                let end_bb = code_gen.context.append_basic_block(function, "while.end");
+                code_gen.builder.build_unconditional_branch(cond_bb)?;
+
+                code_gen.builder.position_at_end(cond_bb);
+                let condition = code_gen.build_condition_of(condition)?;
+                code_gen
+                    .builder
+                    .build_conditional_branch(condition, body_bb, end_bb)?;
+
+                code_gen.builder.position_at_end(body_bb);
+                code_gen.break_targets.borrow_mut().push(end_bb);
+                let result = body.codegen(code_gen);
+                code_gen.break_targets.borrow_mut().pop();
+                result?;
+                if !code_gen.current_block_terminated() {
+                    code_gen.builder.build_unconditional_branch(cond_bb)?;
+                }
+
+                code_gen.builder.position_at_end(end_bb);
+                Ok(())
+            }
+            StatementKind::Switch { condition, body } => code_gen.build_switch(condition, body),
+            StatementKind::Case { value, body } => {
+                let label = code_gen.add_case(value)?;
+                code_gen.start_labelled_block(label)?;
+                body.codegen(code_gen)
+            }
+            StatementKind::Default(_) => unreachable!("lowered with its statement"),
+            StatementKind::Break => {
+                let target = code_gen.break_targets.borrow().last().copied();
+                let target = target
+                    .ok_or_else(|| CodeGenError::new("`break` statement not in loop or switch"))?;
+                code_gen.builder.build_unconditional_branch(target)?;
+                Ok(())
+            }
+            StatementKind::Empty => Ok(()),
+            StatementKind::Error => Err(CodeGenError::new("cannot compile a malformed statement")),
+        }
+    }
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Lowers the body of a `switch` into blocks of its own, then branches
+    /// from the current block to those of the labels found in it.
+    fn build_switch(&self, condition: &Expression, body: &Statement) -> Result<()> {
+        let value = condition.codegen(self)?;
+        if !value.ty.is_integer() {
+            return Err(CodeGenError::at(
+                condition.span,
+                format!(
+                    "switch condition must have integer type, found `{}`",
+                    value.ty
+                ),
+            ));
+        }
+        let ty = value.ty.promoted();
+        let value = self.coerce(value, &ty)?.into_int_value();
+
+        let function = self.current_function().value;
+        let head_bb = self.builder.get_insert_block().unwrap();
+        let body_bb = self.context.append_basic_block(function, "switch.body");
+        let end_bb = self.context.append_basic_block(function, "switch.end");
+
+        self.switches.borrow_mut().push(Switch {
+            ty,
+            cases: Vec::new(),
+            default: None,
+        });
+        self.break_targets.borrow_mut().push(end_bb);
+        self.builder.position_at_end(body_bb);
+        let result = body.codegen(self);
+        self.break_targets.borrow_mut().pop();
+        let switch = self.switches.borrow_mut().pop().unwrap();
+        result?;
+        if !self.current_block_terminated() {
+            self.builder.build_unconditional_branch(end_bb)?;
+        }
+
+        self.builder.position_at_end(head_bb);
+        let cases: Vec<_> = switch
+            .cases
+            .iter()
+            .map(|(case, _, bb)| (value.get_type().const_int(*case, false), *bb))
+            .collect();
+        let default_bb = switch.default.map_or(end_bb, |(_, bb)| bb);
+        self.builder.build_switch(value, default_bb, &cases)?;
+
+        self.builder.position_at_end(end_bb);
+        // every label returns and none breaks out: nothing follows the switch
+        if end_bb.get_first_use().is_none() {
+            self.builder.build_unreachable()?;
+        }
+        Ok(())
+    }
+
+    /// Records a `case` label with the value of `value` in the innermost
+    /// `switch`, returning the block it starts.
+    fn add_case(&self, value: &Expression) -> Result<BasicBlock<'ctx>> {
+        let Some(ty) = self
+            .switches
+            .borrow()
+            .last()
+            .map(|switch| switch.ty.clone())
+        else {
+            return Err(CodeGenError::new(
+                "`case` label not within a switch statement",
+            ));
+        };
+        let not_integer_constant = || {
+            CodeGenError::at(
+                value.span,
+                "case label does not reduce to an integer constant",
+            )
+        };
+        if !has_integer_operands(value) {
+            return Err(not_integer_constant());
+        }
+        let literal = self
+            .evaluate_constant(value)
+            .map_err(|_| not_integer_constant())?;
+        // a cast may still turn an integer into a `float`
+        if let Literal::Float(_) = literal {
+            return Err(not_integer_constant());
+        }
+        let Literal::Int { value: case, .. } = convert(literal, &ty) else {
+            unreachable!("converted to an integer type");
+        };
+
+        let mut switches = self.switches.borrow_mut();
+        let switch = switches.last_mut().unwrap();
+        if let Some((_, previous, _)) = switch.cases.iter().find(|(other, ..)| *other == case) {
+            return Err(CodeGenError::at(
+                value.span,
+                format!("duplicate case value `{}`", int_value(case, &ty)),
+            )
+            .with_secondary(*previous, "previous case is here"));
+        }
+        let function = self.current_function().value;
+        let bb = self.context.append_basic_block(function, "switch.case");
+        switch.cases.push((case, value.span, bb));
+        Ok(bb)
+    }
+
+    /// Records the `default` label at `span` of the innermost `switch`,
+    /// returning the block it starts.
+    fn add_default(&self, span: Span) -> Result<BasicBlock<'ctx>> {
+        let mut switches = self.switches.borrow_mut();
+        let Some(switch) = switches.last_mut() else {
+            return Err(CodeGenError::new(
+                "`default` label not within a switch statement",
+            ));
+        };
+        if let Some((previous, _)) = switch.default {
+            return Err(CodeGenError::new("multiple default labels in one switch")
+                .with_secondary(previous, "previous default is here"));
+        }
+        let function = self.current_function().value;
+        let bb = self.context.append_basic_block(function, "switch.default");
+        switch.default = Some((span, bb));
+        Ok(bb)
+    }
+
+    /// The number of `case` and `default` labels of the innermost switch.
+    fn switch_labels(&self) -> usize {
+        self.switches.borrow().last().map_or(0, |switch| {
+            switch.cases.len() + usize::from(switch.default.is_some())
+        })
+    }
+
+    /// Continues lowering at the block of a label, which the code before it
+    /// falls through to.
+    fn start_labelled_block(&self, bb: BasicBlock<'ctx>) -> Result<()> {
+        if !self.current_block_terminated() {
+            self.builder.build_unconditional_branch(bb)?;
+        }
+        self.builder.position_at_end(bb);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use super::{CodeGen, CodeGenError, Result};
+use crate::ast::{BinaryOperator, Expression, ExpressionKind, Literal, Type, UnaryOperator};
+
+impl CodeGen<'_> {
+    /// Evaluates a constant expression with the enumeration constants and
+    /// typedef names in scope.
+    pub(super) fn evaluate_constant(&self, expr: &Expression) -> Result<Literal> {
+        evaluate(expr, &|name| self.lookup_enumerator(name), &|ty| {
+            self.resolve_type(ty)
+        })
+    }
+
+    /// Whether `expr` is a null pointer constant: an integer constant
+    /// expression with the value 0, such as `0` or `1 - 1`, or one cast to
+    /// `void *` (C11 6.3.2.3).
+    pub(super) fn is_null_pointer_constant(&self, expr: &Expression) -> bool {
+        if let ExpressionKind::Cast { ty, operand } = &expr.kind {
+            if self.resolve_type(ty).is_ok_and(|ty| ty.is_void_pointer()) {
+                return self.is_null_pointer_constant(operand);
+            }
+        }
+        has_integer_operands(expr)
+            && matches!(
+                self.evaluate_constant(expr),
+                Ok(Literal::Int { value: 0, .. })
+            )
+    }
+}
+
+/// Whether the literals `expr` is made of are all integers, as those of an
+/// integer constant expression must be, unless cast to an integer type right
+/// away (C11 6.6p6).
+pub(super) fn has_integer_operands(expr: &Expression) -> bool {
+    match &expr.kind {
+        ExpressionKind::Literal(literal) => matches!(literal, Literal::Int { .. }),
+        ExpressionKind::Cast { operand, .. }
+            if matches!(operand.kind, ExpressionKind::Literal(Literal::Float(_))) =>
+        {
+            true
+        }
+        ExpressionKind::Cast { operand, .. } => has_integer_operands(operand),
+        ExpressionKind::Unary { operand, .. } => has_integer_operands(operand),
+        ExpressionKind::Binary { left, right, .. } => {
+            has_integer_operands(left) && has_integer_operands(right)
+        }
+        _ => true,
+    }
+}
+
+/// Evaluates a constant expression, such as the initializer of a global
+/// variable, with the same conversions and operators as lowered code.
+/// `enumerators` gives the values of the enumeration constants it may use,
+/// and `types` the type each type name in a cast stands for.
+pub(super) fn evaluate(
+    expr: &Expression,
+    enumerators: &impl Fn(&str) -> Option<Literal>,
+    types: &impl Fn(&Type) -> Result<Type>,
+) -> Result<Literal> {
+    evaluate_kind(&expr.kind, enumerators, types).map_err(|e| e.or_span(expr.span))
+}
+
+fn evaluate_kind(
+    kind: &ExpressionKind,
+    enumerators: &impl Fn(&str) -> Option<Literal>,
+    types: &impl Fn(&Type) -> Result<Type>,
+) -> Result<Literal> {
+    match kind {
+        ExpressionKind::Literal(literal) => Ok(literal.clone()),
+        ExpressionKind::Variable(name) => enumerators(name).ok_or_else(not_constant),
+        ExpressionKind::Unary { operator, operand } => {
+            let operand = evaluate(operand, enumerators, types)?;
+            Ok(match (operator, operand) {
+                (UnaryOperator::Plus, Literal::Int { value, ty }) => {
+                    int_constant(int_value(value, &ty), ty.promoted())
+                }
+                (UnaryOperator::Plus, operand) => operand,
+                (UnaryOperator::Negate, Literal::Float(v)) => Literal::Float(-v),
+                (UnaryOperator::Negate, Literal::Int { value, ty }) => {
+                    int_constant(-int_value(value, &ty), ty.promoted())
+                }
+                (UnaryOperator::Not, operand) => {
+                    int_constant(!is_true(&operand) as i128, Type::Int)
+                }
+                (UnaryOperator::Deref | UnaryOperator::AddressOf, _) => {
+                    return Err(not_constant());
+                }
+            })
+        }
+        ExpressionKind::Binary {
+            left,
+            operator: operator @ (BinaryOperator::And | BinaryOperator::Or),
+            right,
+        } => {
+            let left = is_true(&evaluate(left, enumerators, types)?);
+            // as at run time, the right operand is only evaluated when the
+            // left one does not already decide the result
+            let result = match operator {
+                BinaryOperator::And => left && is_true(&evaluate(right, enumerators, types)?),
+                _ => left || is_true(&evaluate(right, enumerators, types)?),
+            };
+            Ok(int_constant(result as i128, Type::Int))
+        }
+        ExpressionKind::Binary {
+            left,
+            operator,
+            right,
+        } => {
+            let left = evaluate(left, enumerators, types)?;
+            let right = evaluate(right, enumerators, types)?;
+            evaluate_binary(operator, left, right)
+        }
+        ExpressionKind::Cast { ty, operand } => {
+            let ty = types(ty)?;
+            if !ty.is_arithmetic() {
+                return Err(not_constant());
+            }
+            Ok(convert(evaluate(operand, enumerators, types)?, &ty))
+        }
+        ExpressionKind::Assignment { .. }
+        | ExpressionKind::FunctionCall { .. }
+        | ExpressionKind::Subscript { .. }
+        | ExpressionKind::Member { .. } => Err(not_constant()),
+        ExpressionKind::Error => Err(CodeGenError::new("cannot compile a malformed expression")),
+    }
+}
+
+pub(super) fn not_constant() -> CodeGenError {
+    CodeGenError::new("initializer element is not a compile-time constant")
+}
+
+/// Converts a constant to the arithmetic type `ty`, as `coerce` does at run
+/// time.
+pub(super) fn convert(literal: Literal, ty: &Type) -> Literal {
+    match (literal, ty) {
+        (Literal::Int { value, ty: from }, Type::Float) => {
+            Literal::Float(int_value(value, &from) as f32)
+        }
+        (Literal::Int { value, ty: from }, to) => int_constant(int_value(value, &from), to.clone()),
+        (Literal::Float(v), Type::Float) => Literal::Float(v),
+        (Literal::Float(v), to) if to.is_signed() => int_constant(v as i64 as i128, to.clone()),
+        (Literal::Float(v), to) => int_constant(v as u64 as i128, to.clone()),
+    }
+}
+
+fn evaluate_binary(operator: &BinaryOperator, left: Literal, right: Literal) -> Result<Literal> {
+    let common = type_of(&left)
+        .common_arithmetic(&type_of(&right))
+        .expect("constants are arithmetic");
+    match (convert(left, &common), convert(right, &common)) {
+        (Literal::Float(l), Literal::Float(r)) => evaluate_float_binary(operator, l, r),
+        (Literal::Int { value: l, .. }, Literal::Int { value: r, .. }) => {
+            let (l, r) = (int_value(l, &common), int_value(r, &common));
+            evaluate_int_binary(operator, common, l, r)
+        }
+        _ => unreachable!("operands converted to a common type"),
+    }
+}
+
+fn evaluate_int_binary(operator: &BinaryOperator, ty: Type, l: i128, r: i128) -> Result<Literal> {
+    let comparison = |result: bool| int_constant(result as i128, Type::Int);
+    let result = match operator {
+        BinaryOperator::Add => int_constant(l + r, ty),
+        BinaryOperator::Subtract => int_constant(l - r, ty),
+        BinaryOperator::Multiply => int_constant(l.wrapping_mul(r), ty),
+        BinaryOperator::Divide | BinaryOperator::Modulus if r == 0 => {
+            return Err(CodeGenError::new("division by zero in constant expression"));
+        }
+        BinaryOperator::Divide => int_constant(l / r, ty),
+        BinaryOperator::Modulus => int_constant(l % r, ty),
+        BinaryOperator::Equal => comparison(l == r),
+        BinaryOperator::NotEqual => comparison(l != r),
+        BinaryOperator::Less => comparison(l < r),
+        BinaryOperator::LessEqual => comparison(l <= r),
+        BinaryOperator::Greater => comparison(l > r),
+        BinaryOperator::GreaterEqual => comparison(l >= r),
+        BinaryOperator::And | BinaryOperator::Or => {
+            unreachable!("logical operators are evaluated by evaluate_kind")
+        }
+    };
+    Ok(result)
+}
+
+fn evaluate_float_binary(operator: &BinaryOperator, l: f32, r: f32) -> Result<Literal> {
+    let comparison = |result: bool| int_constant(result as i128, Type::Int);
+    let result = match operator {
+        BinaryOperator::Add => Literal::Float(l + r),
+        BinaryOperator::Subtract => Literal::Float(l - r),
+        BinaryOperator::Multiply => Literal::Float(l * r),
+        BinaryOperator::Divide => Literal::Float(l / r),
+        BinaryOperator::Modulus => {
+            return Err(CodeGenError::new(
+                "invalid operands to `%`: operands must be integers",
+            ));
+        }
+        BinaryOperator::Equal => comparison(l == r),
+        BinaryOperator::NotEqual => comparison(l != r),
+        BinaryOperator::Less => comparison(l < r),
+        BinaryOperator::LessEqual => comparison(l <= r),
+        BinaryOperator::Greater => comparison(l > r),
+        BinaryOperator::GreaterEqual => comparison(l >= r),
+        BinaryOperator::And | BinaryOperator::Or => {
+            unreachable!("logical operators are evaluated by evaluate_kind")
+        }
+    };
+    Ok(result)
+}
+
+fn type_of(literal: &Literal) -> Type {
+    match literal {
+        Literal::Int { ty, .. } => ty.clone(),
+        Literal::Float(_) => Type::Float,
+    }
+}
+
+fn is_true(literal: &Literal) -> bool {
+    match literal {
+        Literal::Int { value, .. } => *value != 0,
+        Literal::Float(v) => *v != 0.0,
+    }
+}
+
+/// The value of an integer constant of type `ty` whose bits are `bits`.
+pub(super) fn int_value(bits: u64, ty: &Type) -> i128 {
+    let unused = 64 - ty.int_bits().expect("integer constant");
+    if ty.is_signed() {
+        ((bits << unused) as i64 >> unused) as i128
+    } else {
+        bits as i128
+    }
+}
+
+/// An integer constant of type `ty`, wrapping `value` around to its width.
+fn int_constant(value: i128, ty: Type) -> Literal {
+    let unused = 64 - ty.int_bits().expect("integer constant");
+    Literal::Int {
+        value: (value as u64) << unused >> unused,
+        ty,
+    }
+}
+
+#[test]
+fn test_evaluate() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = r#"
+        int a = 2 * 5 + 1;
+        unsigned b = -1;
+        int c = -1 < 0u;
+        long d = -7 / 2 + -7 % 2;
+        float e = 1 / 2.0;
+        int f = 0 && 1 / 0;
+        int g = 2.9;
+        unsigned long h = 0xFFFFFFFFu + 1;
+    "#;
+    let ast = parse(input).unwrap();
+    let values: Vec<_> = ast
+        .program
+        .iter()
+        .map(|item| {
+            let Item::Global(decl) = item else {
+                panic!("expected a global variable");
+            };
+            let value =
+                evaluate(decl.init.as_ref().unwrap(), &|_| None, &|ty| Ok(ty.clone())).unwrap();
+            convert(value, &decl.var_type).to_string()
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [
+            "Int(11)",
+            "Int(4294967295: unsigned int)",
+            "Int(0)",
+            "Int(18446744073709551612: long)",
+            "Float(0.5)",
+            "Int(0)",
+            "Int(2)",
+            "Int(0: unsigned long)",
+        ]
+    );
+
+    let ast = parse("int x = 1 / (2 - 2);").unwrap();
+    let Item::Global(decl) = &ast.program[0] else {
+        panic!("expected a global variable");
+    };
+    let init = decl.init.as_ref().unwrap();
+    let err = evaluate(init, &|_| None, &|ty| Ok(ty.clone())).unwrap_err();
+    assert_eq!(err.msg, "division by zero in constant expression");
+    assert_eq!(err.span, Some(init.span));
+}
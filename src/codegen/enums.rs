@@ -0,0 +1,60 @@
+use super::{CodeGen, CodeGenError, Result, Symbol, constant::int_value};
+use crate::ast::{EnumDef, Literal, Type};
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Defines the enumerated type of `definition`, if it has a tag, and
+    /// declares its enumerators in the current scope. Each enumerator is an
+    /// `int` constant, and a value must be representable as one.
+    pub(super) fn define_enum(&self, definition: &EnumDef) -> Result<()> {
+        if let Some(tag) = &definition.tag {
+            let ty = self.tag_to_define(&Type::Enum(tag.clone()), definition.span)?;
+            let Type::Enum(name) = ty else {
+                unreachable!("an enum tag names an enumerated type");
+            };
+            self.enums.borrow_mut().insert(name, definition.span);
+        }
+
+        let mut next = Some(0);
+        for enumerator in &definition.enumerators {
+            let value = match &enumerator.value {
+                Some(expr) => match self.evaluate_constant(expr)? {
+                    Literal::Int { value, ty } => {
+                        let value = int_value(value, &ty);
+                        i32::try_from(value).map_err(|_| {
+                            CodeGenError::at(
+                                expr.span,
+                                format!(
+                                    "enumerator value `{}` is not representable in `int`",
+                                    value
+                                ),
+                            )
+                        })?
+                    }
+                    Literal::Float(_) => {
+                        return Err(CodeGenError::at(
+                            expr.span,
+                            format!(
+                                "enumerator value for `{}` is not an integer constant",
+                                enumerator.name
+                            ),
+                        ));
+                    }
+                },
+                None => next.ok_or_else(|| {
+                    CodeGenError::at(
+                        enumerator.span,
+                        format!("overflow in enumeration value for `{}`", enumerator.name),
+                    )
+                })?,
+            };
+            next = value.checked_add(1);
+            let value = Literal::Int {
+                value: value as u32 as u64,
+                ty: Type::Int,
+            };
+            self.declare(&enumerator.name, Symbol::Enumerator(value))
+                .map_err(|e| e.or_span(enumerator.span))?;
+        }
+        Ok(())
+    }
+}
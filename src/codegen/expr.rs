@@ -0,0 +1,878 @@
+use inkwell::{
+    AddressSpace, FloatPredicate, IntPredicate,
+    values::{BasicValueEnum, FloatValue, IntValue},
+};
+
+use super::{
+    CodeGen, CodeGenError, CodeGenTrait, Result, Symbol, TypedValue, Variable,
+    into_llvm_type::IntoLlvmType, unexpected_type_name,
+};
+use crate::ast::{
+    BinaryOperator, Expression, ExpressionKind, LValue, LValueKind, Literal, Type, UnaryOperator,
+};
+
+impl<'ctx> CodeGenTrait<'ctx> for Expression {
+    type Ret = Result<TypedValue<'ctx>>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        self.kind
+            .codegen(code_gen)
+            .map_err(|e| e.or_span(self.span))
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for ExpressionKind {
+    type Ret = Result<TypedValue<'ctx>>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match self {
+            ExpressionKind::Literal(lit) => Ok(lit.codegen(code_gen)),
+            ExpressionKind::Variable(name) => match code_gen.lookup(name) {
+                Some(Symbol::Variable(var)) => code_gen.build_load(var, name),
+                Some(Symbol::Enumerator(value)) => Ok(value.codegen(code_gen)),
+                Some(Symbol::Typedef(_)) => Err(unexpected_type_name(name)),
+                None => Err(CodeGenError::new(format!(
+                    "use of undeclared identifier `{}`",
+                    name
+                ))),
+            },
+            ExpressionKind::Assignment { target, value } => {
+                let var = target.codegen(code_gen)?;
+                if var.ty.element().is_some() {
+                    return Err(CodeGenError::at(
+                        target.span,
+                        format!("array type `{}` is not assignable", var.ty),
+                    ));
+                }
+                let value = code_gen.build_coerced(value, &var.ty)?;
+                code_gen.builder.build_store(var.ptr, value)?;
+                Ok(TypedValue { value, ty: var.ty })
+            }
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => {
+                let target = LValue::classify((**operand).clone()).map_err(|operand| {
+                    CodeGenError::at(operand.span, "cannot take the address of an rvalue")
+                })?;
+                let var = target.codegen(code_gen)?;
+                Ok(TypedValue {
+                    value: var.ptr.into(),
+                    ty: Type::Pointer(Box::new(var.ty)),
+                })
+            }
+            ExpressionKind::Unary { operator, operand } => {
+                let operand = operand.codegen(code_gen)?;
+                code_gen.build_unary(operator, operand)
+            }
+            ExpressionKind::Binary {
+                left,
+                operator: operator @ (BinaryOperator::And | BinaryOperator::Or),
+                right,
+            } => code_gen.build_logical(operator, left, right),
+            ExpressionKind::Binary {
+                left,
+                operator,
+                right,
+            } => {
+                let left_value = left.codegen(code_gen)?;
+                let right_value = right.codegen(code_gen)?;
+                // a null pointer constant compares equal to a null pointer
+                // of the type of the other operand, but is added to or
+                // subtracted from a pointer as the integer 0
+                if !operator.is_comparison() {
+                    return code_gen.build_binary(operator, left_value, right_value);
+                }
+                let (left_type, right_type) = (left_value.ty.clone(), right_value.ty.clone());
+                let left_value = code_gen.null_pointer_or(left, left_value, &right_type);
+                let right_value = code_gen.null_pointer_or(right, right_value, &left_type);
+                code_gen.build_binary(operator, left_value, right_value)
+            }
+            ExpressionKind::Subscript { array, index } => {
+                let element = code_gen.build_subscript(array, index)?;
+                code_gen.build_load(element, "elem")
+            }
+            ExpressionKind::Member { object, member } => {
+                let object = match LValue::classify((**object).clone()) {
+                    Ok(object) => object.codegen(code_gen)?,
+                    // a struct returned by value has no storage of its own
+                    Err(object) => {
+                        let value = object.codegen(code_gen)?;
+                        let ptr = code_gen.build_entry_alloca(value.value.get_type(), "tmp")?;
+                        code_gen.builder.build_store(ptr, value.value)?;
+                        Variable { ptr, ty: value.ty }
+                    }
+                };
+                let member = code_gen.build_member(object, member)?;
+                code_gen.build_load(member, "member")
+            }
+            ExpressionKind::FunctionCall { callee, arguments } => code_gen
+                .build_call(callee, arguments)?
+                .ok_or_else(|| CodeGenError::new("void value not ignored as it ought to be")),
+            ExpressionKind::Cast { ty, operand } => {
+                let ty = code_gen.resolve_type(ty)?;
+                if ty == Type::Void {
+                    return Err(CodeGenError::new(
+                        "void value not ignored as it ought to be",
+                    ));
+                }
+                let value = operand.codegen(code_gen)?;
+                let value = code_gen.null_pointer_or(operand, value, &ty);
+                code_gen.build_explicit_cast(value, &ty)
+            }
+            ExpressionKind::Error => {
+                Err(CodeGenError::new("cannot compile a malformed expression"))
+            }
+        }
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for Literal {
+    type Ret = TypedValue<'ctx>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match self {
+            Literal::Int { value, ty } => TypedValue {
+                value: ty
+                    .into_llvm_type(code_gen)
+                    .into_int_type()
+                    .const_int(*value, false)
+                    .into(),
+                ty: ty.clone(),
+            },
+            Literal::Float(n) => TypedValue {
+                value: code_gen.context.f32_type().const_float(*n as f64).into(),
+                ty: Type::Float,
+            },
+        }
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for LValue {
+    type Ret = Result<Variable<'ctx>>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match &self.kind {
+            LValueKind::Var(name) => code_gen.lookup_var(name).map_err(|e| e.or_span(self.span)),
+            LValueKind::Deref(pointer) => {
+                let value = pointer.codegen(code_gen)?;
+                code_gen.pointee_of(value).map_err(|e| e.or_span(self.span))
+            }
+            LValueKind::Subscript { array, index } => code_gen
+                .build_subscript(array, index)
+                .map_err(|e| e.or_span(self.span)),
+            LValueKind::Member { object, member } => {
+                let object = LValue::classify((**object).clone()).map_err(|object| {
+                    CodeGenError::at(object.span, "expression is not assignable")
+                })?;
+                let object = object.codegen(code_gen)?;
+                code_gen
+                    .build_member(object, member)
+                    .map_err(|e| e.or_span(self.span))
+            }
+        }
+    }
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Lowers a call, returning `None` for calls to `void` functions.
+    pub(super) fn build_call(
+        &self,
+        callee: &Expression,
+        arguments: &[Expression],
+    ) -> Result<Option<TypedValue<'ctx>>> {
+        let ExpressionKind::Variable(name) = &callee.kind else {
+            return Err(CodeGenError::at(
+                callee.span,
+                "called object is not a function",
+            ));
+        };
+        let function = self.lookup_function(name).ok_or_else(|| {
+            CodeGenError::at(
+                callee.span,
+                format!("call to undeclared function `{}`", name),
+            )
+        })?;
+        let expected = function.param_types.len();
+        if arguments.len() < expected || (arguments.len() > expected && !function.variadic) {
+            return Err(CodeGenError::new(format!(
+                "function `{}` expects {}{} argument(s), but {} were given",
+                name,
+                if function.variadic { "at least " } else { "" },
+                expected,
+                arguments.len()
+            )));
+        }
+
+        let mut args = Vec::with_capacity(arguments.len());
+        for (arg, param_type) in arguments.iter().zip(&function.param_types) {
+            args.push(self.build_coerced(arg, param_type)?);
+        }
+        for arg in &arguments[expected..] {
+            args.push(self.build_variadic_argument(arg)?);
+        }
+
+        let value = self.build_abi_call(&function, args)?;
+        Ok(value.map(|value| TypedValue {
+            value,
+            ty: function.ret_type,
+        }))
+    }
+
+    /// Lowers an expression statement, whose value is discarded. Only there
+    /// may a call produce no value, and is a cast to `void` of use.
+    pub(super) fn build_discarded(&self, expr: &Expression) -> Result<()> {
+        match &expr.kind {
+            ExpressionKind::FunctionCall { callee, arguments } => {
+                self.build_call(callee, arguments).map(|_| ())
+            }
+            ExpressionKind::Cast { ty, operand }
+                if self.resolve_type(ty).is_ok_and(|ty| ty == Type::Void) =>
+            {
+                self.build_discarded(operand)
+            }
+            _ => expr.codegen(self).map(|_| ()),
+        }
+        .map_err(|e| e.or_span(expr.span))
+    }
+
+    /// Converts `value` to type `to` as a cast does: as `coerce` does, and
+    /// also between any two pointer types, and between pointers and integers
+    /// (C11 6.5.4).
+    fn build_explicit_cast(&self, value: TypedValue<'ctx>, to: &Type) -> Result<TypedValue<'ctx>> {
+        let builder = &self.builder;
+        let converted = match (&value.ty, to) {
+            // pointers are opaque, so only the type changes
+            (Type::Pointer(_), Type::Pointer(_)) => value.value,
+            (Type::Pointer(_), to) if to.is_integer() => builder
+                .build_ptr_to_int(
+                    value.value.into_pointer_value(),
+                    to.into_llvm_type(self).into_int_type(),
+                    "ptrtoint",
+                )?
+                .into(),
+            (from, Type::Pointer(_)) if from.is_integer() => {
+                // widened to the size of a pointer first, as the integer is
+                // signed or not
+                let wide = if from.is_signed() {
+                    Type::Long
+                } else {
+                    Type::UnsignedLong
+                };
+                let value = self.coerce(value, &wide)?;
+                builder
+                    .build_int_to_ptr(
+                        value.into_int_value(),
+                        self.context.ptr_type(AddressSpace::default()),
+                        "inttoptr",
+                    )?
+                    .into()
+            }
+            (from, to) if from.is_arithmetic() && to.is_arithmetic() => self.coerce(value, to)?,
+            (from, to) => {
+                return Err(CodeGenError::new(format!(
+                    "invalid cast from `{}` to `{}`",
+                    from, to
+                )));
+            }
+        };
+        Ok(TypedValue {
+            value: converted,
+            ty: to.clone(),
+        })
+    }
+
+    /// Lowers `expr`, passed for the `...` of a variadic function, with the
+    /// default argument promotions (C11 6.5.2.2p6): integers are promoted
+    /// and a `float` is passed as a `double`.
+    fn build_variadic_argument(&self, expr: &Expression) -> Result<BasicValueEnum<'ctx>> {
+        let value = expr.codegen(self)?;
+        match &value.ty {
+            Type::Float => Ok(self
+                .builder
+                .build_float_ext(
+                    value.value.into_float_value(),
+                    self.context.f64_type(),
+                    "vararg",
+                )?
+                .into()),
+            ty if ty.is_integer() => {
+                let promoted = ty.promoted();
+                self.coerce(value, &promoted)
+            }
+            ty if ty.is_struct_or_union() => Err(CodeGenError::at(
+                expr.span,
+                format!("passing `{}` to a variadic function is not supported", ty),
+            )),
+            _ => Ok(value.value),
+        }
+    }
+
+    /// Lowers `expr` and converts its value to type `to`, as `coerce` does.
+    /// A null pointer constant also converts to any pointer type.
+    pub(super) fn build_coerced(
+        &self,
+        expr: &Expression,
+        to: &Type,
+    ) -> Result<BasicValueEnum<'ctx>> {
+        let value = expr.codegen(self)?;
+        let value = self.null_pointer_or(expr, value, to);
+        self.coerce(value, to).map_err(|e| e.or_span(expr.span))
+    }
+
+    /// A null pointer of type `ty` if `ty` is a pointer type and `expr`, of
+    /// which `value` is the value, is a null pointer constant; otherwise
+    /// `value`. Whether an expression is one depends on its form, not on
+    /// whether its value happens to fold to zero.
+    fn null_pointer_or(
+        &self,
+        expr: &Expression,
+        value: TypedValue<'ctx>,
+        ty: &Type,
+    ) -> TypedValue<'ctx> {
+        if ty.pointee().is_none() || !value.ty.is_integer() || !self.is_null_pointer_constant(expr)
+        {
+            return value;
+        }
+        TypedValue {
+            value: ty
+                .into_llvm_type(self)
+                .into_pointer_type()
+                .const_null()
+                .into(),
+            ty: ty.clone(),
+        }
+    }
+
+    /// Converts `value` to type `to` for assignment, argument passing and
+    /// `return`, inserting the implicit arithmetic conversions of C.
+    pub(super) fn coerce(
+        &self,
+        value: TypedValue<'ctx>,
+        to: &Type,
+    ) -> Result<BasicValueEnum<'ctx>> {
+        let builder = &self.builder;
+        let converted = match (&value.ty, to) {
+            (from, to) if from == to => value.value,
+            (from, to) if from.is_integer() && to.is_integer() => {
+                let target = to.into_llvm_type(self).into_int_type();
+                let v = value.value.into_int_value();
+                // the value keeps its bits; only a wider target has to be
+                // sign- or zero-extended
+                match (from.int_bits(), to.int_bits()) {
+                    (f, t) if f == t => v.into(),
+                    (f, t) if f > t => builder.build_int_truncate(v, target, "trunc")?.into(),
+                    _ if from.is_signed() => builder.build_int_s_extend(v, target, "sext")?.into(),
+                    _ => builder.build_int_z_extend(v, target, "zext")?.into(),
+                }
+            }
+            (from, Type::Float) if from.is_integer() => {
+                let v = value.value.into_int_value();
+                let float = self.context.f32_type();
+                if from.is_signed() {
+                    builder
+                        .build_signed_int_to_float(v, float, "sitofp")?
+                        .into()
+                } else {
+                    builder
+                        .build_unsigned_int_to_float(v, float, "uitofp")?
+                        .into()
+                }
+            }
+            // `void *` converts to and from any other object pointer
+            (Type::Pointer(_), Type::Pointer(_))
+                if value.ty.is_void_pointer() || to.is_void_pointer() =>
+            {
+                value.value
+            }
+            (Type::Float, to) if to.is_integer() => {
+                let v = value.value.into_float_value();
+                let int = to.into_llvm_type(self).into_int_type();
+                if to.is_signed() {
+                    builder.build_float_to_signed_int(v, int, "fptosi")?.into()
+                } else {
+                    builder
+                        .build_float_to_unsigned_int(v, int, "fptoui")?
+                        .into()
+                }
+            }
+            (from, to) => {
+                return Err(CodeGenError::new(format!(
+                    "mismatched types: expected `{}`, found `{}`",
+                    to, from
+                )));
+            }
+        };
+        Ok(converted)
+    }
+
+    /// Evaluates `expr` as a condition.
+    pub(super) fn build_condition_of(&self, expr: &Expression) -> Result<IntValue<'ctx>> {
+        let value = expr.codegen(self)?;
+        self.build_condition(value)
+            .map_err(|e| e.or_span(expr.span))
+    }
+
+    /// Lowers a scalar used as a condition to an `i1` that is true when the
+    /// value compares unequal to zero.
+    fn build_condition(&self, value: TypedValue<'ctx>) -> Result<IntValue<'ctx>> {
+        match (&value.ty, value.value) {
+            (ty, BasicValueEnum::IntValue(v)) if ty.is_integer() => Ok(self
+                .builder
+                .build_int_compare(IntPredicate::NE, v, v.get_type().const_zero(), "tobool")?),
+            (Type::Float, BasicValueEnum::FloatValue(v)) => Ok(self.builder.build_float_compare(
+                FloatPredicate::UNE,
+                v,
+                v.get_type().const_zero(),
+                "tobool",
+            )?),
+            (Type::Pointer(_), BasicValueEnum::PointerValue(v)) => {
+                Ok(self.builder.build_is_not_null(v, "tobool")?)
+            }
+            _ => Err(CodeGenError::new(format!(
+                "value of type `{}` cannot be used as a condition",
+                value.ty
+            ))),
+        }
+    }
+
+    fn build_unary(
+        &self,
+        operator: &UnaryOperator,
+        operand: TypedValue<'ctx>,
+    ) -> Result<TypedValue<'ctx>> {
+        let builder = &self.builder;
+        // the operand of `+` and `-` is promoted, so that `-RED` is an `int`
+        let operand = match operator {
+            UnaryOperator::Plus | UnaryOperator::Negate if operand.ty.is_arithmetic() => {
+                let ty = operand.ty.promoted();
+                TypedValue {
+                    value: self.coerce(operand, &ty)?,
+                    ty,
+                }
+            }
+            _ => operand,
+        };
+        let value: BasicValueEnum = match (operator, operand.value) {
+            (UnaryOperator::Deref, _) => {
+                let var = self.pointee_of(operand)?;
+                return self.build_load(var, "deref");
+            }
+            (UnaryOperator::AddressOf, _) => {
+                unreachable!("`&` is lowered from the lvalue of its operand")
+            }
+            (UnaryOperator::Plus | UnaryOperator::Negate, _) if !operand.ty.is_arithmetic() => {
+                return Err(CodeGenError::new(format!(
+                    "unsupported operand type `{}` for unary `{}`",
+                    operand.ty, operator
+                )));
+            }
+            (UnaryOperator::Plus, value) => value,
+            (UnaryOperator::Negate, BasicValueEnum::IntValue(v)) => {
+                builder.build_int_neg(v, "neg")?.into()
+            }
+            (UnaryOperator::Negate, BasicValueEnum::FloatValue(v)) => {
+                builder.build_float_neg(v, "fneg")?.into()
+            }
+            (UnaryOperator::Not, _) => {
+                let is_true = self.build_condition(operand)?;
+                let is_false = builder.build_not(is_true, "not")?;
+                return Ok(TypedValue {
+                    value: self.bool_to_int(is_false)?.into(),
+                    ty: Type::Int,
+                });
+            }
+            _ => {
+                return Err(CodeGenError::new(format!(
+                    "unsupported operand type `{}` for unary `{}`",
+                    operand.ty, operator
+                )));
+            }
+        };
+        Ok(TypedValue {
+            value,
+            ty: operand.ty,
+        })
+    }
+
+    /// Applies the usual arithmetic conversions to both operands, then
+    /// lowers the operator at the resulting common type.
+    fn build_binary(
+        &self,
+        operator: &BinaryOperator,
+        left: TypedValue<'ctx>,
+        right: TypedValue<'ctx>,
+    ) -> Result<TypedValue<'ctx>> {
+        if left.ty.pointee().is_some() || right.ty.pointee().is_some() {
+            return self.build_pointer_binary(operator, left, right);
+        }
+        let Some(common) = left.ty.common_arithmetic(&right.ty) else {
+            return Err(CodeGenError::new(format!(
+                "unsupported operand types `{}` and `{}` for `{}`",
+                left.ty, right.ty, operator
+            )));
+        };
+        let l = self.coerce(left, &common)?;
+        let r = self.coerce(right, &common)?;
+        match common {
+            Type::Float => {
+                self.build_float_binary(operator, l.into_float_value(), r.into_float_value())
+            }
+            _ => self.build_int_binary(operator, &common, l.into_int_value(), r.into_int_value()),
+        }
+    }
+
+    fn build_int_binary(
+        &self,
+        operator: &BinaryOperator,
+        ty: &Type,
+        l: IntValue<'ctx>,
+        r: IntValue<'ctx>,
+    ) -> Result<TypedValue<'ctx>> {
+        let builder = &self.builder;
+        let signed = ty.is_signed();
+        let arithmetic = |value: IntValue<'ctx>| TypedValue {
+            value: value.into(),
+            ty: ty.clone(),
+        };
+        let comparison = |signed_predicate, unsigned_predicate| -> Result<TypedValue<'ctx>> {
+            let predicate = if signed {
+                signed_predicate
+            } else {
+                unsigned_predicate
+            };
+            Ok(TypedValue {
+                value: self.build_int_comparison(predicate, l, r)?.into(),
+                ty: Type::Int,
+            })
+        };
+        let result = match operator {
+            BinaryOperator::Add => arithmetic(builder.build_int_add(l, r, "add")?),
+            BinaryOperator::Subtract => arithmetic(builder.build_int_sub(l, r, "sub")?),
+            BinaryOperator::Multiply => arithmetic(builder.build_int_mul(l, r, "mul")?),
+            BinaryOperator::Divide if signed => {
+                arithmetic(builder.build_int_signed_div(l, r, "div")?)
+            }
+            BinaryOperator::Divide => arithmetic(builder.build_int_unsigned_div(l, r, "div")?),
+            BinaryOperator::Modulus if signed => {
+                arithmetic(builder.build_int_signed_rem(l, r, "rem")?)
+            }
+            BinaryOperator::Modulus => arithmetic(builder.build_int_unsigned_rem(l, r, "rem")?),
+            BinaryOperator::Equal => comparison(IntPredicate::EQ, IntPredicate::EQ)?,
+            BinaryOperator::NotEqual => comparison(IntPredicate::NE, IntPredicate::NE)?,
+            BinaryOperator::Less => comparison(IntPredicate::SLT, IntPredicate::ULT)?,
+            BinaryOperator::LessEqual => comparison(IntPredicate::SLE, IntPredicate::ULE)?,
+            BinaryOperator::Greater => comparison(IntPredicate::SGT, IntPredicate::UGT)?,
+            BinaryOperator::GreaterEqual => comparison(IntPredicate::SGE, IntPredicate::UGE)?,
+            BinaryOperator::And | BinaryOperator::Or => {
+                unreachable!("logical operators are lowered by build_logical")
+            }
+        };
+        Ok(result)
+    }
+
+    /// Lowers pointer arithmetic and comparisons. An integer added to or
+    /// subtracted from a pointer counts elements of the pointee type, and
+    /// the difference of two pointers is an element count of type `long`.
+    fn build_pointer_binary(
+        &self,
+        operator: &BinaryOperator,
+        left: TypedValue<'ctx>,
+        right: TypedValue<'ctx>,
+    ) -> Result<TypedValue<'ctx>> {
+        let unsupported = |left: &TypedValue, right: &TypedValue| {
+            CodeGenError::new(format!(
+                "unsupported operand types `{}` and `{}` for `{}`",
+                left.ty, right.ty, operator
+            ))
+        };
+        let (left_pointer, right_pointer) = (left.ty.pointee(), right.ty.pointee());
+        match operator {
+            BinaryOperator::Add if left_pointer.is_some() && right.ty.is_integer() => {
+                self.build_pointer_offset(left, right, false)
+            }
+            BinaryOperator::Add if left.ty.is_integer() && right_pointer.is_some() => {
+                self.build_pointer_offset(right, left, false)
+            }
+            BinaryOperator::Subtract if left_pointer.is_some() && right.ty.is_integer() => {
+                self.build_pointer_offset(left, right, true)
+            }
+            BinaryOperator::Subtract if left.ty == right.ty => {
+                let pointee = left_pointer.unwrap();
+                if *pointee == Type::Void {
+                    return Err(CodeGenError::new("arithmetic on a pointer to void"));
+                }
+                if !self.is_complete(pointee) {
+                    return Err(CodeGenError::new(format!(
+                        "arithmetic on a pointer to incomplete type `{}`",
+                        pointee
+                    )));
+                }
+                let difference = self.builder.build_ptr_diff(
+                    pointee.into_llvm_type(self),
+                    left.value.into_pointer_value(),
+                    right.value.into_pointer_value(),
+                    "ptr.diff",
+                )?;
+                Ok(TypedValue {
+                    value: difference.into(),
+                    ty: Type::Long,
+                })
+            }
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual => {
+                // both operands must be pointers; a null pointer constant
+                // has been made a null pointer already
+                let (l, r) = if left.ty == right.ty
+                    || (left_pointer.is_some() && right.ty.is_void_pointer())
+                    || (left.ty.is_void_pointer() && right_pointer.is_some())
+                {
+                    (left.value, right.value)
+                } else {
+                    return Err(unsupported(&left, &right));
+                };
+                let address = |value: BasicValueEnum<'ctx>| {
+                    self.builder.build_ptr_to_int(
+                        value.into_pointer_value(),
+                        self.context.i64_type(),
+                        "addr",
+                    )
+                };
+                let predicate = match operator {
+                    BinaryOperator::Equal => IntPredicate::EQ,
+                    BinaryOperator::NotEqual => IntPredicate::NE,
+                    BinaryOperator::Less => IntPredicate::ULT,
+                    BinaryOperator::LessEqual => IntPredicate::ULE,
+                    BinaryOperator::Greater => IntPredicate::UGT,
+                    _ => IntPredicate::UGE,
+                };
+                let value = self.build_int_comparison(predicate, address(l)?, address(r)?)?;
+                Ok(TypedValue {
+                    value: value.into(),
+                    ty: Type::Int,
+                })
+            }
+            _ => Err(unsupported(&left, &right)),
+        }
+    }
+
+    /// Offsets `pointer` by `offset` elements, backwards if `negate` is set.
+    fn build_pointer_offset(
+        &self,
+        pointer: TypedValue<'ctx>,
+        offset: TypedValue<'ctx>,
+        negate: bool,
+    ) -> Result<TypedValue<'ctx>> {
+        let pointee = pointer.ty.pointee().expect("offset of a non-pointer");
+        if *pointee == Type::Void {
+            return Err(CodeGenError::new("arithmetic on a pointer to void"));
+        }
+        if !self.is_complete(pointee) {
+            return Err(CodeGenError::new(format!(
+                "arithmetic on a pointer to incomplete type `{}`",
+                pointee
+            )));
+        }
+        let mut index = self.coerce(offset, &Type::Long)?.into_int_value();
+        if negate {
+            index = self.builder.build_int_neg(index, "neg")?;
+        }
+        // SAFETY: C leaves pointer arithmetic outside of an array undefined,
+        // which is what `inbounds` tells LLVM
+        let value = unsafe {
+            self.builder.build_in_bounds_gep(
+                pointee.into_llvm_type(self),
+                pointer.value.into_pointer_value(),
+                &[index],
+                "ptr.add",
+            )?
+        };
+        Ok(TypedValue {
+            value: value.into(),
+            ty: pointer.ty,
+        })
+    }
+
+    /// The element `array[index]`, to be loaded from or stored to. As in C,
+    /// this is `*(array + index)`, so either operand may be the pointer.
+    fn build_subscript(&self, array: &Expression, index: &Expression) -> Result<Variable<'ctx>> {
+        let array = array.codegen(self)?;
+        let index = index.codegen(self)?;
+        let (pointer, index) = match (array.ty.pointee(), index.ty.pointee()) {
+            (Some(_), _) if index.ty.is_integer() => (array, index),
+            (_, Some(_)) if array.ty.is_integer() => (index, array),
+            (Some(_), _) | (_, Some(_)) => {
+                return Err(CodeGenError::new("array subscript is not an integer"));
+            }
+            (None, None) => {
+                return Err(CodeGenError::new(format!(
+                    "subscripted value of type `{}` is not an array or pointer",
+                    array.ty
+                )));
+            }
+        };
+        let element = self.build_pointer_offset(pointer, index, false)?;
+        self.pointee_of(element)
+    }
+
+    /// Reads the value of `var`. An array is not loaded but decays to a
+    /// pointer to its first element, which is where it is stored.
+    fn build_load(&self, var: Variable<'ctx>, name: &str) -> Result<TypedValue<'ctx>> {
+        match var.ty {
+            Type::Array(element, _) => Ok(TypedValue {
+                value: var.ptr.into(),
+                ty: Type::Pointer(element),
+            }),
+            ty => {
+                let value = self
+                    .builder
+                    .build_load(ty.into_llvm_type(self), var.ptr, name)?;
+                Ok(TypedValue { value, ty })
+            }
+        }
+    }
+
+    /// The object `pointer` points to, to be loaded from or stored to.
+    fn pointee_of(&self, pointer: TypedValue<'ctx>) -> Result<Variable<'ctx>> {
+        match pointer.ty {
+            Type::Pointer(pointee) if *pointee == Type::Void => {
+                Err(CodeGenError::new("cannot dereference a `void *` pointer"))
+            }
+            Type::Pointer(pointee) if !self.is_complete(&pointee) => Err(CodeGenError::new(
+                format!("incomplete definition of type `{}`", pointee),
+            )),
+            Type::Pointer(pointee) => Ok(Variable {
+                ptr: pointer.value.into_pointer_value(),
+                ty: *pointee,
+            }),
+            ty => Err(CodeGenError::new(format!(
+                "indirection requires a pointer operand, found `{}`",
+                ty
+            ))),
+        }
+    }
+
+    fn build_float_binary(
+        &self,
+        operator: &BinaryOperator,
+        l: FloatValue<'ctx>,
+        r: FloatValue<'ctx>,
+    ) -> Result<TypedValue<'ctx>> {
+        let arithmetic = |value: FloatValue<'ctx>| TypedValue {
+            value: value.into(),
+            ty: Type::Float,
+        };
+        let comparison = |value: IntValue<'ctx>| TypedValue {
+            value: value.into(),
+            ty: Type::Int,
+        };
+        let builder = &self.builder;
+        let result = match operator {
+            BinaryOperator::Add => arithmetic(builder.build_float_add(l, r, "fadd")?),
+            BinaryOperator::Subtract => arithmetic(builder.build_float_sub(l, r, "fsub")?),
+            BinaryOperator::Multiply => arithmetic(builder.build_float_mul(l, r, "fmul")?),
+            BinaryOperator::Divide => arithmetic(builder.build_float_div(l, r, "fdiv")?),
+            BinaryOperator::Modulus => {
+                return Err(CodeGenError::new(
+                    "invalid operands to `%`: operands must be integers",
+                ));
+            }
+            BinaryOperator::Equal => {
+                comparison(self.build_float_comparison(FloatPredicate::OEQ, l, r)?)
+            }
+            BinaryOperator::NotEqual => {
+                comparison(self.build_float_comparison(FloatPredicate::UNE, l, r)?)
+            }
+            BinaryOperator::Less => {
+                comparison(self.build_float_comparison(FloatPredicate::OLT, l, r)?)
+            }
+            BinaryOperator::LessEqual => {
+                comparison(self.build_float_comparison(FloatPredicate::OLE, l, r)?)
+            }
+            BinaryOperator::Greater => {
+                comparison(self.build_float_comparison(FloatPredicate::OGT, l, r)?)
+            }
+            BinaryOperator::GreaterEqual => {
+                comparison(self.build_float_comparison(FloatPredicate::OGE, l, r)?)
+            }
+            BinaryOperator::And | BinaryOperator::Or => {
+                unreachable!("logical operators are lowered by build_logical")
+            }
+        };
+        Ok(result)
+    }
+
+    /// Lowers `&&` and `||` with short-circuit evaluation: the right operand
+    /// is only evaluated when the left one does not already decide the result.
+    fn build_logical(
+        &self,
+        operator: &BinaryOperator,
+        left: &Expression,
+        right: &Expression,
+    ) -> Result<TypedValue<'ctx>> {
+        let function = self.current_function().value;
+        let (rhs_name, end_name) = match operator {
+            BinaryOperator::And => ("land.rhs", "land.end"),
+            _ => ("lor.rhs", "lor.end"),
+        };
+
+        let left = self.build_condition_of(left)?;
+        let left_bb = self.builder.get_insert_block().unwrap();
+        let rhs_bb = self.context.append_basic_block(function, rhs_name);
+        let end_bb = self.context.append_basic_block(function, end_name);
+        let short_circuit = match operator {
+            BinaryOperator::And => {
+                self.builder
+                    .build_conditional_branch(left, rhs_bb, end_bb)?;
+                self.context.bool_type().const_zero()
+            }
+            _ => {
+                self.builder
+                    .build_conditional_branch(left, end_bb, rhs_bb)?;
+                self.context.bool_type().const_all_ones()
+            }
+        };
+
+        self.builder.position_at_end(rhs_bb);
+        let right = self.build_condition_of(right)?;
+        let right_bb = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(end_bb)?;
+
+        self.builder.position_at_end(end_bb);
+        let phi = self
+            .builder
+            .build_phi(self.context.bool_type(), "logical")?;
+        phi.add_incoming(&[(&short_circuit, left_bb), (&right, right_bb)]);
+        let value = self.bool_to_int(phi.as_basic_value().into_int_value())?;
+        Ok(TypedValue {
+            value: value.into(),
+            ty: Type::Int,
+        })
+    }
+
+    /// Comparisons yield an `int` that is either 0 or 1, as in C.
+    fn build_int_comparison(
+        &self,
+        predicate: IntPredicate,
+        l: IntValue<'ctx>,
+        r: IntValue<'ctx>,
+    ) -> Result<IntValue<'ctx>> {
+        let cmp = self.builder.build_int_compare(predicate, l, r, "cmp")?;
+        self.bool_to_int(cmp)
+    }
+
+    fn build_float_comparison(
+        &self,
+        predicate: FloatPredicate,
+        l: FloatValue<'ctx>,
+        r: FloatValue<'ctx>,
+    ) -> Result<IntValue<'ctx>> {
+        let cmp = self.builder.build_float_compare(predicate, l, r, "fcmp")?;
+        self.bool_to_int(cmp)
+    }
+
+    fn bool_to_int(&self, value: IntValue<'ctx>) -> Result<IntValue<'ctx>> {
+        let int_type = self.context.i32_type();
+        Ok(self.builder.build_int_z_extend(value, int_type, "zext")?)
+    }
+}
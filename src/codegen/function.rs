@@ -0,0 +1,184 @@
+use super::{
+    CodeGen, CodeGenError, CodeGenTrait, FunctionEntry, Result, Variable, block::codegen_items,
+};
+use crate::{
+    ast::{Block, Function, Type},
+    diagnostics::Diagnostic,
+};
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Adds `func` to the functions of the module, or checks it against an
+    /// earlier declaration of the same name. Every function is declared
+    /// before any body is lowered, so that a call may precede the definition
+    /// of its callee; one that is never defined stays an external
+    /// declaration, to be resolved by the linker.
+    pub(super) fn declare_function(&self, func: &Function) -> Result<()> {
+        let ret_type = self
+            .resolve_type(&func.ret_type)
+            .map_err(|e| e.or_span(func.span))?;
+        let param_types = func
+            .params
+            .iter()
+            .map(|p| {
+                self.resolve_type(&p.param_type)
+                    .map_err(|e| e.or_span(p.span))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let params = func.params.iter().zip(&param_types);
+
+        if let Some((param, _)) = params.clone().find(|(_, ty)| **ty == Type::Void) {
+            let msg = match &param.name {
+                Some(name) => format!(
+                    "parameter `{}` of function `{}` has type void",
+                    name, func.name
+                ),
+                None => format!("parameter of function `{}` has type void", func.name),
+            };
+            return Err(CodeGenError::at(param.span, msg));
+        }
+        // structs are passed by value, so they must be defined by now
+        if let Some((param, _)) = params.clone().find(|(_, ty)| !self.is_complete(ty)) {
+            let msg = match &param.name {
+                Some(name) => format!(
+                    "parameter `{}` of function `{}` has incomplete type `{}`",
+                    name, func.name, param.param_type
+                ),
+                None => format!(
+                    "parameter of function `{}` has incomplete type `{}`",
+                    func.name, param.param_type
+                ),
+            };
+            return Err(CodeGenError::at(param.span, msg));
+        }
+        if ret_type != Type::Void && !self.is_complete(&ret_type) {
+            return Err(CodeGenError::at(
+                func.span,
+                format!(
+                    "function `{}` returns incomplete type `{}`",
+                    func.name, func.ret_type
+                ),
+            ));
+        }
+
+        if let Some(previous) = self.lookup_function(&func.name) {
+            if previous.ret_type != ret_type
+                || previous.param_types != param_types
+                || previous.variadic != func.variadic
+            {
+                return Err(CodeGenError::at(
+                    func.span,
+                    format!("conflicting types for `{}`", func.name),
+                )
+                .with_secondary(previous.span, "previous declaration is here"));
+            }
+            if func.body.is_some() {
+                if previous.defined {
+                    return Err(CodeGenError::at(
+                        func.span,
+                        format!("redefinition of function `{}`", func.name),
+                    )
+                    .with_secondary(previous.span, "previous definition is here"));
+                }
+                self.functions.borrow_mut().insert(
+                    func.name.clone(),
+                    FunctionEntry {
+                        span: func.span,
+                        defined: true,
+                        ..previous
+                    },
+                );
+            }
+            return Ok(());
+        }
+
+        let (fn_type, signature) = self.lower_signature(&ret_type, &param_types, func.variadic);
+        let entry = FunctionEntry {
+            value: self.module.add_function(&func.name, fn_type, None),
+            ret_type,
+            param_types,
+            variadic: func.variadic,
+            signature,
+            span: func.span,
+            defined: func.body.is_some(),
+        };
+        self.add_abi_attributes(&entry);
+        self.functions.borrow_mut().insert(func.name.clone(), entry);
+        Ok(())
+    }
+}
+
+impl<'ctx> CodeGenTrait<'ctx> for Function {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        // a prototype has nothing to lower beyond its declaration
+        let Some(body) = &self.body else {
+            return Ok(());
+        };
+        let entry = code_gen
+            .lookup_function(&self.name)
+            .expect("function lowered before being declared");
+        let function = entry.value;
+        *code_gen.current_function.borrow_mut() = Some(entry);
+
+        let basic_block = code_gen.context.append_basic_block(function, "entry");
+        code_gen.builder.position_at_end(basic_block);
+
+        // parameters and the outermost block of the body share one scope
+        code_gen.push_scope();
+        let result = self.codegen_body(code_gen, body);
+        code_gen.pop_scope();
+        *code_gen.current_function.borrow_mut() = None;
+        result.map_err(|e| e.or_span(self.span))
+    }
+}
+
+impl Function {
+    fn codegen_body<'ctx>(&self, code_gen: &CodeGen<'ctx>, body: &Block) -> Result<()> {
+        let names: Vec<_> = self
+            .params
+            .iter()
+            .map(|param| param.name.as_deref().unwrap_or_default())
+            .collect();
+        let slots = code_gen.build_params(&names)?;
+        // the types of the declaration, with typedef names resolved
+        let FunctionEntry {
+            ret_type,
+            param_types,
+            ..
+        } = code_gen.current_function();
+        for ((param, ty), ptr) in self.params.iter().zip(param_types).zip(slots) {
+            // unnamed parameters are reported by the parser
+            let Some(name) = &param.name else {
+                continue;
+            };
+            code_gen
+                .declare_var(name, Variable { ptr, ty })
+                .map_err(|e| e.or_span(param.span))?;
+        }
+
+        codegen_items(&body.items, code_gen)?;
+
+        // falling off the end of a function returns zero, as `main` must
+        if !code_gen.current_block_terminated() {
+            match ret_type {
+                Type::Void => code_gen.build_return_value(None)?,
+                _ => {
+                    if self.name != "main" {
+                        let end = body.span.end;
+                        code_gen.warn(
+                            Diagnostic::warning(format!(
+                                "non-void function `{}` does not return a value in all control paths",
+                                self.name
+                            ))
+                            .at((end - 1..end).into())
+                            .with_note("falling off the end returns 0"),
+                        );
+                    }
+                    let zero = code_gen.const_zero(&ret_type);
+                    code_gen.build_return_value(Some(zero))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use inkwell::values::BasicValueEnum;
+
+use super::{
+    CodeGen, CodeGenError, CodeGenTrait, Result, Symbol, TypedValue, Variable,
+    constant::{convert, not_constant},
+    into_llvm_type::IntoLlvmType,
+};
+use crate::ast::{Declaration, Expression, ExpressionKind, Item, Type, UnaryOperator};
+
+impl<'ctx> CodeGenTrait<'ctx> for Item {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        match self {
+            Item::Function(func) => func.codegen(code_gen),
+            // defined along with the declarations of functions
+            Item::Struct(_) | Item::Enum(_) | Item::Typedef(_) => Ok(()),
+            Item::Global(decl) => code_gen
+                .build_global(decl)
+                .map_err(|e| e.or_span(decl.span)),
+        }
+    }
+}
+
+impl<'ctx> CodeGen<'ctx> {
+    /// Emits a file-scope variable as an LLVM global. Its initializer must be
+    /// a constant expression; without one it starts out as zero. A
+    /// declaration without an initializer is a tentative definition (C11
+    /// 6.9.2), which may be repeated, so it refers to the same global as
+    /// other declarations of the name, of which only one may be initialized.
+    fn build_global(&self, decl: &Declaration) -> Result<()> {
+        let ty = self.resolve_type(&decl.var_type)?;
+        self.check_variable_type(&decl.name, &ty)?;
+        if self.lookup_function(&decl.name).is_some() {
+            return Err(CodeGenError::new(format!(
+                "redefinition of `{}` as a different kind of symbol",
+                decl.name
+            )));
+        }
+
+        let previous = match self.lookup(&decl.name) {
+            Some(Symbol::Variable(previous)) => Some(previous),
+            _ => None,
+        };
+        if let Some(previous) = &previous {
+            if previous.ty != ty {
+                return Err(CodeGenError::new(format!(
+                    "redefinition of `{}` with a different type (`{}` vs `{}`)",
+                    decl.name, ty, previous.ty
+                )));
+            }
+            let initialized = self.initialized_globals.borrow().get(&decl.name).copied();
+            if let (Some(_), Some(span)) = (&decl.init, initialized) {
+                return Err(
+                    CodeGenError::new(format!("redefinition of `{}`", decl.name))
+                        .with_secondary(span, "previous definition is here"),
+                );
+            }
+        }
+
+        let initializer = match &decl.init {
+            Some(init) if ty.element().is_some() => {
+                return Err(CodeGenError::at(
+                    init.span,
+                    "array initializer must be an initializer list",
+                ));
+            }
+            Some(init) if ty.pointee().is_some() => self.build_constant_pointer(init, &ty)?,
+            // constants are never structs, so this only reports the mismatch
+            Some(init) if ty.is_struct_or_union() => {
+                let value = self.evaluate_constant(init)?.codegen(self);
+                self.coerce(value, &ty).map_err(|e| e.or_span(init.span))?
+            }
+            Some(init) => {
+                convert(self.evaluate_constant(init)?, &ty)
+                    .codegen(self)
+                    .value
+            }
+            None if previous.is_some() => return Ok(()),
+            None => self.const_zero(&ty),
+        };
+        if decl.init.is_some() {
+            self.initialized_globals
+                .borrow_mut()
+                .insert(decl.name.clone(), decl.span);
+        }
+        let global = match previous {
+            Some(_) => self
+                .module
+                .get_global(&decl.name)
+                .expect("file-scope variables are globals"),
+            None => {
+                let global = self
+                    .module
+                    .add_global(ty.into_llvm_type(self), None, &decl.name);
+                self.declare_var(
+                    &decl.name,
+                    Variable {
+                        ptr: global.as_pointer_value(),
+                        ty,
+                    },
+                )?;
+                global
+            }
+        };
+        global.set_initializer(&initializer);
+        Ok(())
+    }
+
+    /// The initializer of a file-scope pointer: a null pointer constant,
+    /// possibly cast to another pointer type, or the address of another
+    /// global variable.
+    fn build_constant_pointer(&self, init: &Expression, ty: &Type) -> Result<BasicValueEnum<'ctx>> {
+        let value = match &init.kind {
+            ExpressionKind::Unary {
+                operator: UnaryOperator::AddressOf,
+                operand,
+            } => match &operand.kind {
+                ExpressionKind::Variable(name) => {
+                    let var = self.lookup_var(name).map_err(|e| e.or_span(operand.span))?;
+                    TypedValue {
+                        value: var.ptr.into(),
+                        ty: Type::Pointer(Box::new(var.ty)),
+                    }
+                }
+                _ => return Err(not_constant().or_span(init.span)),
+            },
+            _ if self.is_null_pointer_constant(init) => {
+                return Ok(ty
+                    .into_llvm_type(self)
+                    .into_pointer_type()
+                    .const_null()
+                    .into());
+            }
+            ExpressionKind::Cast {
+                ty: target,
+                operand,
+            } if self.is_null_pointer_constant(operand) => {
+                let target = self
+                    .resolve_type(target)
+                    .map_err(|e| e.or_span(init.span))?;
+                if target.pointee().is_none() {
+                    return Err(not_constant().or_span(init.span));
+                }
+                TypedValue {
+                    value: target
+                        .into_llvm_type(self)
+                        .into_pointer_type()
+                        .const_null()
+                        .into(),
+                    ty: target,
+                }
+            }
+            _ => self.evaluate_constant(init)?.codegen(self),
+        };
+        self.coerce(value, ty).map_err(|e| e.or_span(init.span))
+    }
+}
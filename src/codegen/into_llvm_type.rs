@@ -1,17 +1,39 @@
-use inkwell::types::BasicMetadataTypeEnum;
+use inkwell::{
+    AddressSpace,
+    types::{BasicType, BasicTypeEnum},
+};
 
 use crate::{ast::Type, codegen::CodeGen};
 
 pub trait IntoLlvmType<'ctx> {
-    fn into_llvm_type(&self, code_gen: &CodeGen<'ctx>) -> BasicMetadataTypeEnum<'ctx>;
+    fn into_llvm_type(&self, code_gen: &CodeGen<'ctx>) -> BasicTypeEnum<'ctx>;
 }
 
 impl<'ctx> IntoLlvmType<'ctx> for Type {
-    fn into_llvm_type(&self, code_gen: &CodeGen<'ctx>) -> BasicMetadataTypeEnum<'ctx> {
+    fn into_llvm_type(&self, code_gen: &CodeGen<'ctx>) -> BasicTypeEnum<'ctx> {
         match self {
-            Type::Int => BasicMetadataTypeEnum::IntType(code_gen.context.i32_type()),
-            Type::Float => BasicMetadataTypeEnum::FloatType(code_gen.context.f32_type()),
-            Type::Void => panic!("Void type cannot be used as a function parameter or return type"),
+            Type::Char | Type::UnsignedChar => BasicTypeEnum::IntType(code_gen.context.i8_type()),
+            Type::Int | Type::UnsignedInt | Type::Enum(_) => {
+                BasicTypeEnum::IntType(code_gen.context.i32_type())
+            }
+            Type::Long | Type::UnsignedLong | Type::LongLong | Type::UnsignedLongLong => {
+                BasicTypeEnum::IntType(code_gen.context.i64_type())
+            }
+            Type::Float => BasicTypeEnum::FloatType(code_gen.context.f32_type()),
+            // pointers are opaque; loads and GEPs name the pointee type
+            Type::Pointer(_) => {
+                BasicTypeEnum::PointerType(code_gen.context.ptr_type(AddressSpace::default()))
+            }
+            Type::Array(element, len) => element.into_llvm_type(code_gen).array_type(*len).into(),
+            Type::Struct(_) | Type::Union(_) => code_gen
+                .struct_entry(self)
+                .expect("incomplete types are rejected before lowering")
+                .llvm_type
+                .into(),
+            // `void` values are rejected before lowering; `void` returns use
+            // `Context::void_type` directly
+            Type::Void => unreachable!("`void` has no LLVM value type"),
+            Type::Typedef(_) => unreachable!("typedef names are resolved before lowering"),
         }
     }
 }
@@ -1,71 +1,500 @@
-use inkwell::{builder::Builder, context::Context, module::Module, types::BasicMetadataTypeEnum};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, hash_map::Entry},
+};
 
-use crate::ast::{Ast, Function};
+use inkwell::{
+    basic_block::BasicBlock,
+    builder::{Builder, BuilderError},
+    context::Context,
+    module::Module,
+    targets::{TargetMachine, TargetTriple},
+    types::{BasicType, BasicTypeEnum, StructType},
+    values::{BasicValueEnum, FunctionValue, PointerValue},
+};
+
+use crate::{
+    ast::{Ast, Item, Literal, Span, StructKind, Type, Typedef},
+    diagnostics::{Diagnostic, Label},
+};
+use abi::{Abi, Signature};
 use into_llvm_type::IntoLlvmType;
 
+mod abi;
+mod block;
+mod constant;
+mod enums;
+mod expr;
+mod function;
+mod global;
 mod into_llvm_type;
+mod structs;
 mod test;
 
 struct CodeGen<'ctx> {
     context: &'ctx Context,
     module: Module<'ctx>,
     builder: Builder<'ctx>,
+    functions: RefCell<HashMap<String, FunctionEntry<'ctx>>>,
+    /// The defined struct and union types, by the name their tag is
+    /// declared with in `tags`.
+    structs: RefCell<HashMap<String, StructEntry<'ctx>>>,
+    /// Where each enumerated type is defined, by the name its tag is
+    /// declared with in `tags`.
+    enums: RefCell<HashMap<String, Span>>,
+    scopes: RefCell<Vec<HashMap<String, Symbol<'ctx>>>>,
+    /// The struct, union and enum tags declared in each of `scopes`, which
+    /// share a namespace of their own, with the type each one names.
+    tags: RefCell<Vec<HashMap<String, Type>>>,
+    /// How many types have been given each tag, to name each one uniquely.
+    tag_uses: RefCell<HashMap<String, u32>>,
+    /// Where each file-scope variable with an initializer is defined, as a
+    /// tentative definition may precede or follow it.
+    initialized_globals: RefCell<HashMap<String, Span>>,
+    current_function: RefCell<Option<FunctionEntry<'ctx>>>,
+    /// Where `break` jumps to, for each loop or `switch` being lowered.
+    break_targets: RefCell<Vec<BasicBlock<'ctx>>>,
+    /// The labels found so far in each `switch` being lowered.
+    switches: RefCell<Vec<Switch<'ctx>>>,
+    warnings: RefCell<Vec<Diagnostic>>,
+    /// The calling convention structs are passed with.
+    abi: Abi,
+}
+
+/// A function known to the module, with its source-level signature.
+#[derive(Clone)]
+struct FunctionEntry<'ctx> {
+    value: FunctionValue<'ctx>,
+    ret_type: Type,
+    param_types: Vec<Type>,
+    /// Whether further arguments may follow those for `param_types`.
+    variadic: bool,
+    /// How the parameters and the return value map to those of `value`.
+    signature: Signature<'ctx>,
+    /// The definition, or the first declaration while there is none.
+    span: Span,
+    defined: bool,
+}
+
+/// A defined struct or union type.
+#[derive(Clone)]
+struct StructEntry<'ctx> {
+    kind: StructKind,
+    llvm_type: StructType<'ctx>,
+    members: Vec<(String, Type)>,
+    span: Span,
+}
+
+/// A variable: where it is stored (a stack slot, or a global for file-scope
+/// variables) and its source-level type.
+#[derive(Clone)]
+struct Variable<'ctx> {
+    ptr: PointerValue<'ctx>,
+    ty: Type,
+}
+
+/// What an identifier in scope names.
+#[derive(Clone)]
+enum Symbol<'ctx> {
+    Variable(Variable<'ctx>),
+    /// An enumeration constant, an `int` constant.
+    Enumerator(Literal),
+    /// A typedef name, with the type it stands for.
+    Typedef(Type),
+}
+
+/// A `switch` statement whose body is being lowered.
+struct Switch<'ctx> {
+    /// The promoted type of the controlling expression, which case values
+    /// are converted to.
+    ty: Type,
+    /// The value of each `case` label, where it is, and the block it starts.
+    cases: Vec<(u64, Span, BasicBlock<'ctx>)>,
+    default: Option<(Span, BasicBlock<'ctx>)>,
+}
+
+/// An rvalue produced by lowering an expression.
+#[derive(Clone)]
+struct TypedValue<'ctx> {
+    value: BasicValueEnum<'ctx>,
+    ty: Type,
 }
 
 impl<'ctx> CodeGen<'ctx> {
+    /// A code generator for the host.
     pub fn new_with_module_name(context: &'ctx Context, module_name: &str) -> Self {
+        Self::new_for_target(context, module_name, &TargetMachine::get_default_triple())
+    }
+
+    pub fn new_for_target(
+        context: &'ctx Context,
+        module_name: &str,
+        triple: &TargetTriple,
+    ) -> Self {
         let module = context.create_module(module_name);
+        module.set_triple(triple);
         let builder = context.create_builder();
         Self {
             context,
             module,
             builder,
+            functions: RefCell::new(HashMap::new()),
+            structs: RefCell::new(HashMap::new()),
+            enums: RefCell::new(HashMap::new()),
+            scopes: RefCell::new(Vec::new()),
+            tags: RefCell::new(Vec::new()),
+            tag_uses: RefCell::new(HashMap::new()),
+            initialized_globals: RefCell::new(HashMap::new()),
+            current_function: RefCell::new(None),
+            break_targets: RefCell::new(Vec::new()),
+            switches: RefCell::new(Vec::new()),
+            warnings: RefCell::new(Vec::new()),
+            abi: Abi::for_triple(&triple.as_str().to_string_lossy()),
         }
     }
-}
 
-trait CodeGenTrait {
-    type Ret;
-    fn codegen(&self, code_gen: &CodeGen) -> Self::Ret;
+    fn warn(&self, warning: Diagnostic) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    fn push_scope(&self) {
+        self.scopes.borrow_mut().push(HashMap::new());
+        self.tags.borrow_mut().push(HashMap::new());
+    }
+
+    fn pop_scope(&self) {
+        self.scopes.borrow_mut().pop();
+        self.tags.borrow_mut().pop();
+    }
+
+    fn declare(&self, name: &str, symbol: Symbol<'ctx>) -> Result<()> {
+        let mut scopes = self.scopes.borrow_mut();
+        let scope = scopes.last_mut().expect("declaration outside of any scope");
+        match scope.entry(name.to_owned()) {
+            Entry::Occupied(_) => Err(CodeGenError::new(format!("redefinition of `{}`", name))),
+            Entry::Vacant(slot) => {
+                slot.insert(symbol);
+                Ok(())
+            }
+        }
+    }
+
+    fn declare_var(&self, name: &str, var: Variable<'ctx>) -> Result<()> {
+        self.declare(name, Symbol::Variable(var))
+    }
+
+    /// Declares the typedef name of `typedef` in the current scope. As in
+    /// C11, a typedef may be repeated with the same type.
+    fn define_typedef(&self, typedef: &Typedef) -> Result<()> {
+        let ty = self
+            .resolve_type(&typedef.ty)
+            .map_err(|e| e.or_span(typedef.span))?;
+        let previous = self
+            .scopes
+            .borrow()
+            .last()
+            .and_then(|scope| scope.get(&typedef.name).cloned());
+        match previous {
+            Some(Symbol::Typedef(previous)) if previous == ty => Ok(()),
+            Some(Symbol::Typedef(previous)) => Err(CodeGenError::at(
+                typedef.span,
+                format!(
+                    "typedef redefinition with different types (`{}` vs `{}`)",
+                    ty, previous
+                ),
+            )),
+            _ => self
+                .declare(&typedef.name, Symbol::Typedef(ty))
+                .map_err(|e| e.or_span(typedef.span)),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Symbol<'ctx>> {
+        self.scopes
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    /// The variable `name` refers to, which must designate an object.
+    fn lookup_var(&self, name: &str) -> Result<Variable<'ctx>> {
+        match self.lookup(name) {
+            Some(Symbol::Variable(var)) => Ok(var),
+            Some(Symbol::Enumerator(_)) => Err(CodeGenError::new(format!(
+                "enumeration constant `{}` is not an lvalue",
+                name
+            ))),
+            Some(Symbol::Typedef(_)) => Err(unexpected_type_name(name)),
+            None => Err(CodeGenError::new(format!(
+                "use of undeclared identifier `{}`",
+                name
+            ))),
+        }
+    }
+
+    /// The value of the enumeration constant `name`, if it is one.
+    fn lookup_enumerator(&self, name: &str) -> Option<Literal> {
+        match self.lookup(name) {
+            Some(Symbol::Enumerator(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn lookup_function(&self, name: &str) -> Option<FunctionEntry<'ctx>> {
+        self.functions.borrow().get(name).cloned()
+    }
+
+    fn current_function(&self) -> FunctionEntry<'ctx> {
+        self.current_function
+            .borrow()
+            .clone()
+            .expect("statement lowered outside of a function")
+    }
+
+    /// Allocates a stack slot in the entry block of the current function, so
+    /// that every local lives in a single `alloca` regardless of where it is
+    /// declared.
+    fn build_entry_alloca(
+        &self,
+        ty: impl BasicType<'ctx>,
+        name: &str,
+    ) -> Result<PointerValue<'ctx>> {
+        let entry = self
+            .current_function()
+            .value
+            .get_first_basic_block()
+            .expect("function without an entry block");
+        let builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(inst) => builder.position_before(&inst),
+            None => builder.position_at_end(entry),
+        }
+        Ok(builder.build_alloca(ty, name)?)
+    }
+
+    /// Whether the block being built already ends in a terminator, in which
+    /// case anything emitted after it would be unreachable.
+    fn current_block_terminated(&self) -> bool {
+        self.builder
+            .get_insert_block()
+            .and_then(|bb| bb.get_terminator())
+            .is_some()
+    }
+
+    /// `ty` with the typedef names and struct, union and enum tags in it replaced
+    /// by the types they stand for in the current scope. Types are resolved
+    /// where they enter lowering, in declarations, so that lowering never
+    /// sees a typedef name, and a tag always names the type it did there.
+    fn resolve_type(&self, ty: &Type) -> Result<Type> {
+        Ok(match ty {
+            Type::Typedef(name) => match self.lookup(name) {
+                Some(Symbol::Typedef(ty)) => ty,
+                _ => {
+                    return Err(CodeGenError::new(format!("unknown type name `{}`", name)));
+                }
+            },
+            Type::Pointer(pointee) => Type::Pointer(Box::new(self.resolve_type(pointee)?)),
+            Type::Array(element, len) => Type::Array(Box::new(self.resolve_type(element)?), *len),
+            Type::Struct(_) | Type::Union(_) | Type::Enum(_) => self.resolve_tag(ty)?,
+            ty => ty.clone(),
+        })
+    }
+
+    /// Whether objects of type `ty` can be created: `void`, and structs,
+    /// unions and enums that are not defined (yet), have no size.
+    fn is_complete(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Void => false,
+            Type::Struct(_) | Type::Union(_) => self.struct_entry(ty).is_some(),
+            Type::Enum(tag) => self.enums.borrow().contains_key(tag),
+            Type::Array(element, _) => self.is_complete(element),
+            _ => true,
+        }
+    }
+
+    /// Checks that a variable named `name` can be given type `ty`, which
+    /// must be complete.
+    fn check_variable_type(&self, name: &str, ty: &Type) -> Result<()> {
+        let mut element = ty;
+        while let Some(inner) = element.element() {
+            element = inner;
+        }
+        match ty {
+            _ if self.is_complete(ty) => Ok(()),
+            Type::Void => Err(CodeGenError::new(format!(
+                "variable `{}` declared void",
+                name
+            ))),
+            Type::Array(..) => Err(CodeGenError::new(format!(
+                "array `{}` has incomplete element type `{}`",
+                name, element
+            ))),
+            _ => Err(CodeGenError::new(format!(
+                "variable `{}` has incomplete type `{}`",
+                name, ty
+            ))),
+        }
+    }
+
+    fn const_zero(&self, ty: &Type) -> BasicValueEnum<'ctx> {
+        match ty.into_llvm_type(self) {
+            BasicTypeEnum::IntType(ty) => ty.const_zero().into(),
+            BasicTypeEnum::FloatType(ty) => ty.const_zero().into(),
+            BasicTypeEnum::PointerType(ty) => ty.const_null().into(),
+            BasicTypeEnum::ArrayType(ty) => ty.const_zero().into(),
+            BasicTypeEnum::StructType(ty) => ty.const_zero().into(),
+            _ => unreachable!("no zero value for type {}", ty),
+        }
+    }
 }
 
-impl CodeGenTrait for Ast {
-    type Ret = ();
-    fn codegen(&self, code_gen: &CodeGen) -> Self::Ret {
-        for func in &self.program {
-            func.codegen(code_gen);
+/// Lowers `ast` into a fresh, verified LLVM module named `module_name`, which
+/// follows the C calling convention of `triple`. Warnings, and the error on
+/// failure, are appended to `diagnostics`.
+pub fn compile<'ctx>(
+    context: &'ctx Context,
+    ast: &Ast,
+    module_name: &str,
+    triple: &TargetTriple,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Option<Module<'ctx>> {
+    let code_gen = CodeGen::new_for_target(context, module_name, triple);
+    let result = ast.codegen(&code_gen).and_then(|()| {
+        code_gen
+            .module
+            .verify()
+            .map_err(|e| CodeGenError::new(format!("invalid module: {}", e.to_string_lossy())))
+    });
+    diagnostics.append(&mut code_gen.warnings.borrow_mut());
+    match result {
+        Ok(()) => Some(code_gen.module),
+        Err(err) => {
+            diagnostics.push(err.into());
+            None
         }
     }
 }
 
-impl CodeGenTrait for Function {
-    type Ret = ();
-    fn codegen(&self, code_gen: &CodeGen) -> Self::Ret {
-        println!("Generating code for function: {}", self.name);
+pub type Result<T> = std::result::Result<T, CodeGenError>;
+
+fn unexpected_type_name(name: &str) -> CodeGenError {
+    CodeGenError::new(format!(
+        "unexpected type name `{}`: expected expression",
+        name
+    ))
+}
+
+trait CodeGenTrait<'ctx> {
+    type Ret;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret;
+}
 
-        let ret_type = self.ret_type.into_llvm_type(code_gen);
-        let param_types = self
-            .params
+impl<'ctx> CodeGenTrait<'ctx> for Ast {
+    type Ret = Result<()>;
+    fn codegen(&self, code_gen: &CodeGen<'ctx>) -> Self::Ret {
+        // file-scope variables live in the outermost scope, below those of
+        // the function being lowered
+        code_gen.push_scope();
+        let result = self
+            .program
             .iter()
-            .map(|p| p.param_type.into_llvm_type(code_gen))
-            .collect::<Vec<_>>();
-        let fn_type = match ret_type {
-            BasicMetadataTypeEnum::IntType(ty) => ty.fn_type(&param_types, false),
-            _ => panic!("Unsupported return type"),
-        };
+            .try_for_each(|item| match item {
+                Item::Function(func) => code_gen.declare_function(func),
+                Item::Struct(definition) => code_gen.define_struct(definition),
+                Item::Enum(definition) => code_gen.define_enum(definition),
+                Item::Typedef(typedef) => code_gen.define_typedef(typedef),
+                Item::Global(_) => Ok(()),
+            })
+            .and_then(|()| {
+                self.program
+                    .iter()
+                    .try_for_each(|item| item.codegen(code_gen))
+            });
+        code_gen.pop_scope();
+        result
+    }
+}
+
+/// A semantic error found while lowering, located at the innermost node that
+/// could be blamed for it.
+#[derive(Debug, Clone)]
+pub struct CodeGenError {
+    msg: String,
+    span: Option<Span>,
+    secondary: Vec<Label>,
+}
+
+impl CodeGenError {
+    fn new(msg: impl Into<String>) -> Self {
+        CodeGenError {
+            msg: msg.into(),
+            span: None,
+            secondary: Vec::new(),
+        }
+    }
+
+    fn at(span: Span, msg: impl Into<String>) -> Self {
+        CodeGenError {
+            msg: msg.into(),
+            span: Some(span),
+            secondary: Vec::new(),
+        }
+    }
+
+    /// Attributes the error to `span` unless a more precise location is
+    /// already known.
+    fn or_span(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
 
-        // 用真正的函数名
+    /// Points at a related location, such as an earlier declaration.
+    fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+}
 
-        let function = code_gen.module.add_function(&self.name, fn_type, None);
-        let basic_block = code_gen.context.append_basic_block(function, "entry");
-        code_gen.builder.position_at_end(basic_block);
+impl From<CodeGenError> for Diagnostic {
+    fn from(err: CodeGenError) -> Self {
+        let mut diagnostic = Diagnostic::error(err.msg);
+        diagnostic.span = err.span;
+        diagnostic.secondary = err.secondary;
+        diagnostic
+    }
+}
 
-        // build body ...
+impl From<BuilderError> for CodeGenError {
+    fn from(err: BuilderError) -> Self {
+        CodeGenError::new(format!("LLVM builder error: {}", err))
+    }
+}
 
-        code_gen.builder.build_return(None).unwrap();
+impl std::fmt::Display for CodeGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CodeGenError: {}", self.msg)
     }
 }
 
+impl std::error::Error for CodeGenError {}
+
+/// Lowers `input`, which must parse, and checks that code generation fails
+/// with `message` at the part of `input` that reads `at`.
+#[cfg(test)]
+fn assert_codegen_error(input: &str, message: &str, at: &str) {
+    let ast = crate::parser::parse(input).unwrap();
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+    let err = ast.codegen(&codegen).unwrap_err();
+    assert_eq!(err.msg, message, "{}", input);
+    let span = err.span.unwrap();
+    assert_eq!(&input[span.start..span.end], at, "{}", input);
+}
+
 #[test]
 fn test() {
     use crate::parser::parse;
@@ -76,7 +505,1293 @@ fn test() {
     let context = Context::create();
     let codegen = CodeGen::new_with_module_name(&context, "my_module");
 
-    ast.codegen(&codegen);
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_expressions() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int add(int x, int y) {
+          int ans = x + y;
+          return ans;
+        }
+
+        int main() {
+          int a = 3;
+          int b;
+          b = add(a, 7) * 2 % 5;
+          if (b != 0 && add(b, 1) == 1 || !a) {
+            b = 1;
+          }
+          return -b + (a = 1);
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_float() {
+    use crate::parser::parse;
+
+    let input = r#"
+        float scale(float x, int n) {
+          float k = .5;
+          if (x >= 1e2 || n % 2 == 0) {
+            k = 2.5e-3;
+          }
+          return x * k + n;
+        }
+
+        int main() {
+          float y = scale(3, 4);
+          int truncated = y * 100.;
+          return -truncated / 2;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_void() {
+    use crate::parser::parse;
+
+    let input = r#"
+        void nothing() {}
+
+        void early(int x) {
+          if (x) return;
+          nothing();
+        }
+
+        int main() {
+          early(1);
+          return 0;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_integer_literals() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int main() {
+          int mask = 0xFF;
+          int wrapped = -1 < 0u;
+          int wide = 3000000000 - 2999999999 == 1l;
+          float f = 0x10 + 010u + 0b10ll;
+          return mask + wrapped + wide + (4000000000u / 3 % 7ul > 2) + f;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_void_errors() {
+    for (input, message, at) in [
+        (
+            "void f(void x) {}",
+            "parameter `x` of function `f` has type void",
+            "void x",
+        ),
+        (
+            "int main() { void x; return 0; }",
+            "variable `x` declared void",
+            "void x;",
+        ),
+        (
+            "void f() { return 1; }",
+            "void function should not return a value",
+            "return 1;",
+        ),
+        (
+            "int main() { return; }",
+            "non-void function should return a value",
+            "return;",
+        ),
+        (
+            "void f() {} int main() { int x = f(); return x; }",
+            "void value not ignored as it ought to be",
+            "f()",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_globals() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int counter;
+        int limit = 2 * 5 + 1;
+        float scale = 1 / 2.0;
+        unsigned long mask = -1;
+
+        int bump() {
+          counter = counter + 1;
+          return counter;
+        }
+
+        int main() {
+          int limit = 3;
+          while (bump() < limit) ;
+          return counter * scale + mask % 7;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+    let limit = codegen.module.get_global("limit").unwrap();
+    assert_eq!(
+        limit
+            .get_initializer()
+            .unwrap()
+            .into_int_value()
+            .get_zero_extended_constant(),
+        Some(11)
+    );
+}
+
+#[test]
+fn test_global_errors() {
+    for (input, message, at) in [
+        (
+            "int x = y;",
+            "initializer element is not a compile-time constant",
+            "y",
+        ),
+        (
+            "int f() { return 0; } int x = f();",
+            "initializer element is not a compile-time constant",
+            "f()",
+        ),
+        (
+            "int x = 1 / 0;",
+            "division by zero in constant expression",
+            "1 / 0",
+        ),
+        (
+            "float x = 1.5 % 2;",
+            "invalid operands to `%`: operands must be integers",
+            "1.5 % 2",
+        ),
+        ("void x;", "variable `x` declared void", "void x;"),
+        (
+            "int f() { return 0; } int f;",
+            "redefinition of `f` as a different kind of symbol",
+            "int f;",
+        ),
+        (
+            "int x; int x() { return 0; }",
+            "redefinition of `x` as a different kind of symbol",
+            "int x;",
+        ),
+        (
+            "int main() { return g; } int g;",
+            "use of undeclared identifier `g`",
+            "g",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_tentative_definitions() {
+    use crate::parser::parse;
+
+    let ast = parse(
+        "int x; int x = 3; int x;
+int main() { return x; }",
+    )
+    .unwrap();
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+    let x = codegen.module.get_global("x").unwrap();
+    assert_eq!(
+        x.get_initializer()
+            .unwrap()
+            .into_int_value()
+            .get_zero_extended_constant(),
+        Some(3)
+    );
+
+    for (input, message, at) in [
+        (
+            "int x = 1; int x; int x = 2;",
+            "redefinition of `x`",
+            "int x = 2;",
+        ),
+        (
+            "int x; float x;",
+            "redefinition of `x` with a different type (`float` vs `int`)",
+            "float x;",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_prototypes() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int helper(int);
+        extern int abs(int x);
+        float twice(float x);
+
+        int main(void) {
+          return helper(2) + abs(-3) + twice(1.5);
+        }
+
+        int helper(int n) { return n * 2; }
+        float twice(float x) { return x * 2; }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+    let abs = codegen.module.get_function("abs").unwrap();
+    assert_eq!(abs.count_basic_blocks(), 0);
+}
+
+#[test]
+fn test_prototype_errors() {
+    use crate::parser::parse;
+
+    for (input, message, at) in [
+        (
+            "int f(int); float f(int x) { return x; }",
+            "conflicting types for `f`",
+            "float f(int x) { return x; }",
+        ),
+        (
+            "int f(int); int f(float x) { return 0; }",
+            "conflicting types for `f`",
+            "int f(float x) { return 0; }",
+        ),
+        (
+            "int f(int x, int y); int f(int x) { return x; }",
+            "conflicting types for `f`",
+            "int f(int x) { return x; }",
+        ),
+        (
+            "int f() { return 0; } int f() { return 1; }",
+            "redefinition of function `f`",
+            "int f() { return 1; }",
+        ),
+        (
+            "int f(void, int);",
+            "parameter of function `f` has type void",
+            "void",
+        ),
+        (
+            "int main() { return g(); }",
+            "call to undeclared function `g`",
+            "g",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+
+    let input = "int f(int);
+float f(int x) { return x; }
+";
+    let mut diagnostics = Vec::new();
+    let context = Context::create();
+    assert!(
+        compile(
+            &context,
+            &parse(input).unwrap(),
+            "my_module",
+            &TargetMachine::get_default_triple(),
+            &mut diagnostics
+        )
+        .is_none()
+    );
+    assert_eq!(diagnostics[0].message, "conflicting types for `f`");
+    assert_eq!(
+        diagnostics[0].secondary[0].message,
+        "previous declaration is here"
+    );
+    assert_eq!(diagnostics[0].secondary[0].span.start, 0);
+}
+
+#[test]
+fn test_chars_and_variadic_functions() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int printf(char *format, ...);
+        int puts(char *s);
+
+        char last(char *s, unsigned char n) { return s[n - 1]; }
+
+        int main(void) {
+          char text[3];
+          char c = 200;
+          text[0] = c;
+          text[1] = -c;
+          text[2] = 0;
+          puts(text);
+          printf(text, c, 1.5, text, last(text, 2));
+          return c + last(text, 2);
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+    let printf = codegen.module.get_function("printf").unwrap();
+    assert!(printf.get_type().is_var_arg());
+    assert_eq!(printf.count_params(), 1);
+    let last = codegen.module.get_function("last").unwrap();
+    assert!(!last.get_type().is_var_arg());
+    assert_eq!(
+        last.get_type().get_return_type(),
+        Some(context.i8_type().into())
+    );
+
+    for (input, message, at) in [
+        (
+            "int printf(char *format, ...); int main() { return printf(); }",
+            "function `printf` expects at least 1 argument(s), but 0 were given",
+            "printf()",
+        ),
+        (
+            "int f(char *format); int f(char *format, ...);",
+            "conflicting types for `f`",
+            "int f(char *format, ...);",
+        ),
+        (
+            "struct s { int x; }; int f(int n, ...); int main() { struct s v; return f(1, v); }",
+            "passing `struct s` to a variadic function is not supported",
+            "v",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_pointers() {
+    use crate::parser::parse;
+
+    let input = r#"
+        int counter;
+        int *global = &counter;
+        float *nothing = 0;
+
+        void swap(int *a, int *b) {
+          int t = *a;
+          *a = *b;
+          *b = t;
+        }
+
+        int *first(int *p) { return p; }
+
+        int main() {
+          int x = 1;
+          int y = 2;
+          int *px = &x;
+          int **ppx = &px;
+          swap(px, &y);
+          **ppx = *px + 1;
+          int *q = 1 + px;
+          long d = q - px;
+          if (px != 0 && q > px && !(px == q) && nothing == 0) {
+            x = x + d;
+          }
+          void *v = px;
+          int *back = v;
+          int *null = 0;
+          int *folded = first(2 * 3 - 6);
+          *global = *first(back - 0u) + (null == 0) + !null;
+          return counter;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_pointer_errors() {
+    for (input, message, at) in [
+        (
+            "int main() { int x; int *p = x; return 0; }",
+            "mismatched types: expected `int *`, found `int`",
+            "x",
+        ),
+        (
+            "int main() { float *f; int *p = f; return 0; }",
+            "mismatched types: expected `int *`, found `float *`",
+            "f",
+        ),
+        (
+            "int main() { int *p = 1; return 0; }",
+            "mismatched types: expected `int *`, found `int`",
+            "1",
+        ),
+        (
+            "int main() { return *1; }",
+            "indirection requires a pointer operand, found `int`",
+            "*1",
+        ),
+        (
+            "int main() { int *p = &1; return 0; }",
+            "cannot take the address of an rvalue",
+            "1",
+        ),
+        (
+            "int main() { int x = 1; *x = 2; return 0; }",
+            "indirection requires a pointer operand, found `int`",
+            "*x",
+        ),
+        (
+            "int main() { int *p; int *q; p + q; return 0; }",
+            "unsupported operand types `int *` and `int *` for `+`",
+            "p + q",
+        ),
+        (
+            "int main() { int *p; p * 2; return 0; }",
+            "unsupported operand types `int *` and `int` for `*`",
+            "p * 2",
+        ),
+        (
+            "int main() { int *p; float *q; return p == q; }",
+            "unsupported operand types `int *` and `float *` for `==`",
+            "p == q",
+        ),
+        (
+            "int main() { int *p; return p < 1; }",
+            "unsupported operand types `int *` and `int` for `<`",
+            "p < 1",
+        ),
+        (
+            "int main() { int *p; -p; return 0; }",
+            "unsupported operand type `int *` for unary `-`",
+            "-p",
+        ),
+        (
+            "int main() { void *v; *v; return 0; }",
+            "cannot dereference a `void *` pointer",
+            "*v",
+        ),
+        (
+            "int main() { void *v; v + 1; return 0; }",
+            "arithmetic on a pointer to void",
+            "v + 1",
+        ),
+        (
+            "int main() { int *p; return p; }",
+            "mismatched types: expected `int`, found `int *`",
+            "p",
+        ),
+        (
+            "int x; int *p = x;",
+            "initializer element is not a compile-time constant",
+            "x",
+        ),
+        (
+            "int x = 1; float *p = &x;",
+            "mismatched types: expected `float *`, found `int *`",
+            "&x",
+        ),
+        // only integer constant expressions are null pointer constants,
+        // whatever the builder folds
+        (
+            "int main() { int x; int *p = (x = 0); return 0; }",
+            "mismatched types: expected `int *`, found `int`",
+            "(x = 0)",
+        ),
+        (
+            "int f() { return 1; } int main() { int *p = 0 * f(); return 0; }",
+            "mismatched types: expected `int *`, found `int`",
+            "0 * f()",
+        ),
+        (
+            "int main() { int *p; int x; return p == (x = 0); }",
+            "unsupported operand types `int *` and `int` for `==`",
+            "p == (x = 0)",
+        ),
+        (
+            "int main() { int *p = 0.0 * 1; return 0; }",
+            "mismatched types: expected `int *`, found `float`",
+            "0.0 * 1",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_arrays() {
+    use crate::parser::parse;
+
+    let input = r#"
+        float table[8];
+
+        float trace(float m[4][4]) {
+          float sum = 0.0;
+          int i = 0;
+          while (i < 4) {
+            sum = sum + m[i][i];
+            i = i + 1;
+          }
+          return sum;
+        }
+
+        int sum(int *v, int n) {
+          int total = 0;
+          while (n > 0) {
+            n = n - 1;
+            total = total + v[n];
+          }
+          return total;
+        }
+
+        int main() {
+          int a[10];
+          float m[4][4];
+          int i = 0;
+          while (i < 10) {
+            a[i] = i;
+            i = i + 1;
+          }
+          m[1][2] = table[3] + 1;
+          float *row = m[1];
+          int *p = &a[2];
+          *p = 2[a] + *a;
+          return sum(a, 10) + trace(m) + row[2];
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_array_errors() {
+    for (input, message, at) in [
+        (
+            "int main() { int a[2]; int b[2]; a = b; return 0; }",
+            "array type `int [2]` is not assignable",
+            "a",
+        ),
+        (
+            "int main() { int a[2]; float f; return a[f]; }",
+            "array subscript is not an integer",
+            "a[f]",
+        ),
+        (
+            "int main() { int x; return x[0]; }",
+            "subscripted value of type `int` is not an array or pointer",
+            "x[0]",
+        ),
+        (
+            "int main() { int a[2] = 0; return 0; }",
+            "array initializer must be an initializer list",
+            "0",
+        ),
+        (
+            "int main() { void a[2]; return 0; }",
+            "array `a` has incomplete element type `void`",
+            "void a[2];",
+        ),
+        (
+            "int main() { int m[2][3]; int *p = m; return 0; }",
+            "mismatched types: expected `int *`, found `int (*)[3]`",
+            "m",
+        ),
+        (
+            "int a[2] = 1;",
+            "array initializer must be an initializer list",
+            "1",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_structs() {
+    use crate::parser::parse;
+
+    let input = r#"
+        struct point { int x; float y; };
+        struct segment { struct point from; struct point to; int tag[3]; };
+
+        struct point origin;
+        struct segment axis;
+
+        struct point make(int x, float y) {
+          struct point p;
+          p.x = x;
+          p.y = y;
+          return p;
+        }
+
+        struct segment flip(struct segment s) {
+          struct point t = s.from;
+          s.from = s.to;
+          s.to = t;
+          return s;
+        }
+
+        float length(struct segment *s) {
+          return s->to.x - s->from.x + s->to.y - s->from.y;
+        }
+
+        int main() {
+          struct pair { long a; long b; } q;
+          struct segment s;
+          s.from = make(1, 2.0);
+          s.to = origin;
+          s.tag[0] = 97;
+          q.a = make(3, 4.0).x;
+          q.b = q.a;
+          axis = flip(s);
+          struct point *p = &axis.to;
+          p->x = 5;
+          return length(&axis) + q.b + (*p).x;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_struct_errors() {
+    for (input, message, at) in [
+        (
+            "struct s { int x; }; int main() { struct s v; return v.y; }",
+            "no member named `y` in `struct s`",
+            "v.y",
+        ),
+        (
+            "int main() { int x; return x.y; }",
+            "member reference base type `int` is not a structure or union",
+            "x.y",
+        ),
+        (
+            "struct s { int x; }; int main() { struct s v; return v->x; }",
+            "indirection requires a pointer operand, found `struct s`",
+            "v",
+        ),
+        (
+            "struct s { int x; }; struct s { int y; };",
+            "redefinition of `struct s`",
+            "struct s { int y; }",
+        ),
+        (
+            "struct s { int x; float x; };",
+            "duplicate member `x`",
+            "float x;",
+        ),
+        (
+            "struct s { struct s inner; };",
+            "member `inner` has incomplete type `struct s`",
+            "struct s inner;",
+        ),
+        (
+            "int main() { struct missing v; return 0; }",
+            "variable `v` has incomplete type `struct missing`",
+            "struct missing v;",
+        ),
+        (
+            "struct s { int x; }; int main() { struct s v; int i = v; return 0; }",
+            "mismatched types: expected `int`, found `struct s`",
+            "v",
+        ),
+        (
+            "struct s { int x; }; struct t { int x; }; int main() { struct s a; struct t b; a = b; return 0; }",
+            "mismatched types: expected `struct s`, found `struct t`",
+            "b",
+        ),
+        (
+            "struct s { int x; }; int main() { struct s v; return v + 1; }",
+            "unsupported operand types `struct s` and `int` for `+`",
+            "v + 1",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_struct_scopes() {
+    use crate::parser::parse;
+
+    let input = r#"
+        struct tmp { int x; };
+        struct later *forward;
+
+        int f() {
+          struct tmp { float y; } t;
+          t.y = 1.5;
+          return t.y;
+        }
+
+        int g() {
+          struct tmp { long z; } t;
+          t.z = 2;
+          return t.z;
+        }
+
+        int h() {
+          struct tmp outer;
+          outer.x = 1;
+          {
+            struct tmp { float y; } inner;
+            inner.y = 2.0;
+            outer.x = outer.x + inner.y;
+          }
+          return outer.x;
+        }
+
+        struct later { int v; };
+
+        int main() {
+          return f() + g() + h() + forward->v;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
 
-    codegen.module.print_to_stderr();
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+
+    for (input, message, at) in [
+        (
+            "int f() { struct s { int x; } a; return 0; } int g() { struct s b; return 0; }",
+            "variable `b` has incomplete type `struct s`",
+            "struct s b;",
+        ),
+        (
+            "struct s { int x; }; int main() { struct s { float y; } v; return v.x; }",
+            "no member named `x` in `struct s`",
+            "v.x",
+        ),
+        (
+            "int main() { struct s { int x; }; { struct s { int y; }; } struct s { int z; }; return 0; }",
+            "redefinition of `struct s`",
+            "struct s { int z; }",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_enums_and_unions() {
+    use crate::parser::parse;
+
+    let input = r#"
+        enum state { IDLE, RUNNING = 5, STOPPED, LAST = STOPPED * 2 };
+        enum { MASK = 0xFF };
+        union word { int i; float f; long l; };
+        struct message { enum state state; union word payload; };
+
+        enum state current = RUNNING;
+        int table[4];
+        union word zero;
+
+        enum state step(enum state s) {
+          if (s == IDLE) {
+            return RUNNING;
+          }
+          return STOPPED;
+        }
+
+        union word decode(struct message *m) {
+          union word w = m->payload;
+          if (m->state == STOPPED) {
+            w.i = w.i % MASK;
+          }
+          return w;
+        }
+
+        int main() {
+          enum local { ONE = 1, TWO };
+          struct message m;
+          m.state = step(current);
+          m.payload.f = 1.5;
+          union word w = decode(&m);
+          int STOPPED = TWO;
+          return w.i + m.state + STOPPED + table[ONE] + LAST;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_enum_scopes() {
+    use crate::parser::parse;
+
+    let input = r#"
+        enum mode { OFF, ON };
+
+        int f() {
+          enum mode { SLOW = 2, FAST };
+          enum mode m = FAST;
+          return m;
+        }
+
+        int g() {
+          enum mode { SLOW = 7, FAST };
+          enum mode m = SLOW;
+          return -m;
+        }
+
+        int main() {
+          enum mode outer = ON;
+          {
+            enum mode { ON = 5 };
+            outer = outer + ON;
+          }
+          return f() + g() + outer + ON;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+
+    for (input, message, at) in [
+        (
+            "int f() { enum e { A }; return A; } int g() { enum e v; return 0; }",
+            "variable `v` has incomplete type `enum e`",
+            "enum e v;",
+        ),
+        (
+            "int f() { enum e { A }; return 0; } int g() { return A; }",
+            "use of undeclared identifier `A`",
+            "A",
+        ),
+        (
+            "int main() { enum e { A }; struct e { int x; }; return 0; }",
+            "use of `e` with tag type that does not match previous declaration",
+            "struct e { int x; }",
+        ),
+        // the operand of `-` is promoted to `int`
+        (
+            "enum e { A }; struct s { int x; }; int main() { enum e v = A; struct s w; w = -v; return 0; }",
+            "mismatched types: expected `struct s`, found `int`",
+            "-v",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_switch_statements() {
+    use crate::parser::parse;
+
+    let input = r#"
+        enum color { RED, GREEN, BLUE };
+
+        int describe(enum color c) {
+          int result = 0;
+          switch (c) {
+            case RED:
+              result = 10;
+              break;
+            case GREEN:
+            case BLUE + 1:
+              result = 20;
+            default:
+              result = result + 1;
+          }
+          return result;
+        }
+
+        int sign(long x) {
+          switch (x < 0) {
+            case 1: return -1;
+            default: return x != 0;
+          }
+        }
+
+        int nested(int x) {
+          switch (x) {
+            case 1: return 1;
+            { case 2: return 2; }
+          }
+          return 0;
+        }
+
+        int main() {
+          int i = 0;
+          while (1) {
+            switch (i) {
+              case 3: break;
+            }
+            if (i == 5) break;
+            i = i + 1;
+          }
+          return describe(GREEN) + sign(-4) + i;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+
+    for (input, message, at) in [
+        (
+            "int main() { switch (1) { case 1: case 2 - 1: return 0; } return 1; }",
+            "duplicate case value `1`",
+            "2 - 1",
+        ),
+        (
+            "int main() { int x = 1; switch (2) { case x: return 0; } return 1; }",
+            "case label does not reduce to an integer constant",
+            "x",
+        ),
+        (
+            "int main() { switch (2) { case 1.0: return 0; } return 1; }",
+            "case label does not reduce to an integer constant",
+            "1.0",
+        ),
+        (
+            "int main() { case 1: return 0; }",
+            "`case` label not within a switch statement",
+            "case 1: return 0;",
+        ),
+        (
+            "int main() { switch (0) { default: ; default: ; } return 0; }",
+            "multiple default labels in one switch",
+            "default: ;",
+        ),
+        (
+            "int main() { break; return 0; }",
+            "`break` statement not in loop or switch",
+            "break;",
+        ),
+        (
+            "int main() { float f = 1.0; switch (f) { } return 0; }",
+            "switch condition must have integer type, found `float`",
+            "f",
+        ),
+        (
+            "int main() { return 0; 1 + y; }",
+            "use of undeclared identifier `y`",
+            "y",
+        ),
+        (
+            "int main() { switch (0) { case 0: break; { case 0: ; } } return 0; }",
+            "duplicate case value `0`",
+            "0",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_enum_and_union_errors() {
+    for (input, message, at) in [
+        ("enum e { A, B, A };", "redefinition of `A`", "A"),
+        ("enum e { A }; int A;", "redefinition of `A`", "int A;"),
+        (
+            "enum e { A = 1.5 };",
+            "enumerator value for `A` is not an integer constant",
+            "1.5",
+        ),
+        (
+            "enum e { A = 0x80000000 };",
+            "enumerator value `2147483648` is not representable in `int`",
+            "0x80000000",
+        ),
+        (
+            "enum e { A = 0x7FFFFFFF, B };",
+            "overflow in enumeration value for `B`",
+            "B",
+        ),
+        (
+            "int main() { int x; enum e { A = x }; return 0; }",
+            "initializer element is not a compile-time constant",
+            "x",
+        ),
+        (
+            "enum e { A }; int main() { A = 1; return 0; }",
+            "enumeration constant `A` is not an lvalue",
+            "A",
+        ),
+        (
+            "enum e { A }; int main() { int *p = &A; return 0; }",
+            "enumeration constant `A` is not an lvalue",
+            "A",
+        ),
+        (
+            "enum e { A }; enum e { B };",
+            "redefinition of `enum e`",
+            "enum e { B }",
+        ),
+        (
+            "struct s { int x; }; union s { int x; };",
+            "use of `s` with tag type that does not match previous declaration",
+            "union s { int x; }",
+        ),
+        (
+            "enum s { A }; struct s { int x; };",
+            "use of `s` with tag type that does not match previous declaration",
+            "struct s { int x; }",
+        ),
+        (
+            "union u { int x; }; int main() { struct u v; return 0; }",
+            "use of `u` with tag type that does not match previous declaration",
+            "struct u v;",
+        ),
+        (
+            "int main() { enum missing e; return 0; }",
+            "variable `e` has incomplete type `enum missing`",
+            "enum missing e;",
+        ),
+        (
+            "union u { int x; }; int main() { union u v; return v.y; }",
+            "no member named `y` in `union u`",
+            "v.y",
+        ),
+        (
+            "int main() { enum e { A }; return 0; } int f() { return A; }",
+            "use of undeclared identifier `A`",
+            "A",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_typedefs() {
+    use crate::parser::parse;
+
+    let input = r#"
+        typedef int score_t;
+        typedef score_t total_t;
+        typedef struct node node;
+        struct node { score_t value; node *next; };
+        typedef struct { float x; float y; } point;
+        typedef int score_t;
+        typedef node *list[2];
+
+        total_t sum(node *n) {
+          score_t total = 0;
+          while (n != 0) {
+            total = total + n->value;
+            n = n->next;
+          }
+          return total;
+        }
+
+        point scale(point p, float k) {
+          p.x = p.x * k;
+          p.y = p.y * k;
+          return p;
+        }
+
+        int main() {
+          node a;
+          node b;
+          list l;
+          a.value = 1;
+          a.next = &b;
+          b.value = 2;
+          b.next = 0;
+          l[0] = &a;
+          point p;
+          p.x = 1.5;
+          {
+            typedef float score_t;
+            score_t half = 0.5;
+            p.y = half;
+          }
+          point q = scale(p, 2.0);
+          int node = sum(l[0]);
+          return node + q.y;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+}
+
+#[test]
+fn test_casts() {
+    use crate::parser::parse;
+
+    let input = r#"
+        typedef long wide;
+        typedef struct point { int x; } point;
+        int *null = (int *)0;
+        float half = (float)1 / 2;
+        enum { SIZE = (int)2.5 + (wide)1 };
+
+        int main(void) {
+          long n = 300;
+          char c = (char)n;
+          unsigned char u = (unsigned char)-1;
+          int *p = (void *)0;
+          void *v = (void *)p;
+          long address = (long)v;
+          point pt;
+          point *pp = (point *)(void *)&pt;
+          (void)n;
+          switch (n) {
+            case (int)300L: n = (wide)SIZE;
+          }
+          return (int)(float)c + u + (int)address + (p == (void *)0) + (int)n + pp->x;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+
+    let context = Context::create();
+    let codegen = CodeGen::new_with_module_name(&context, "my_module");
+
+    ast.codegen(&codegen).unwrap();
+    codegen.module.verify().unwrap();
+
+    for (input, message, at) in [
+        (
+            "struct s { int x; }; int main() { struct s v; return (int)v; }",
+            "invalid cast from `struct s` to `int`",
+            "(int)v",
+        ),
+        (
+            "int main() { float f = 1.0; int *p = (int *)f; return 0; }",
+            "invalid cast from `float` to `int *`",
+            "(int *)f",
+        ),
+        (
+            "int main() { int x = (void)1; return x; }",
+            "void value not ignored as it ought to be",
+            "(void)1",
+        ),
+        // a pointer cast to `int *` is not a null pointer constant
+        (
+            "int main() { int *p = 0; float *q = (int *)0; return 0; }",
+            "mismatched types: expected `float *`, found `int *`",
+            "(int *)0",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
+}
+
+#[test]
+fn test_typedef_errors() {
+    for (input, message, at) in [
+        (
+            "typedef int t; typedef float t;",
+            "typedef redefinition with different types (`float` vs `int`)",
+            "typedef float t;",
+        ),
+        ("typedef int t; int t;", "redefinition of `t`", "int t;"),
+        ("int t; typedef int t;", "redefinition of `t`", "int t;"),
+        ("typedef void v; v x;", "variable `x` declared void", "v x;"),
+        (
+            "typedef struct node node; node n;",
+            "variable `n` has incomplete type `struct node`",
+            "node n;",
+        ),
+        (
+            "typedef struct node node; int f(node n);",
+            "parameter `n` of function `f` has incomplete type `node`",
+            "node n",
+        ),
+        (
+            "typedef struct node node; node f();",
+            "function `f` returns incomplete type `node`",
+            "node f();",
+        ),
+        (
+            "typedef int t; int main() { typedef float t; typedef int t; return 0; }",
+            "typedef redefinition with different types (`int` vs `float`)",
+            "typedef int t;",
+        ),
+        (
+            "typedef struct { int x; } a; typedef struct { int x; } b; a f(b v) { return v; }",
+            "mismatched types: expected `anonymous struct`, found `anonymous struct`",
+            "v",
+        ),
+    ] {
+        assert_codegen_error(input, message, at);
+    }
 }
@@ -0,0 +1,326 @@
+use std::collections::{HashSet, hash_map::Entry};
+
+use inkwell::types::BasicTypeEnum;
+
+use super::{CodeGen, CodeGenError, Result, StructEntry, Variable, into_llvm_type::IntoLlvmType};
+use crate::ast::{Span, StructDef, StructKind, Type};
+
+impl<'ctx> CodeGen<'ctx> {
+    /// The type `tag` names in the innermost scope that declares it.
+    fn lookup_tag(&self, tag: &str) -> Option<Type> {
+        self.tags
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(tag).cloned())
+    }
+
+    /// Declares `tag` in the current scope as naming a new type of the same
+    /// kind as `ty`. Types are named by their tag, with a number added for
+    /// all but the first type given it, so that the same tag declared in
+    /// different scopes names different types.
+    fn declare_tag(&self, tag: &str, ty: &Type) -> Type {
+        let mut uses = self.tag_uses.borrow_mut();
+        let count = uses.entry(tag.to_owned()).or_default();
+        let name = match *count {
+            0 => tag.to_owned(),
+            n => format!("{}.{}", tag, n),
+        };
+        *count += 1;
+        let declared = renamed(ty, name);
+        self.tags
+            .borrow_mut()
+            .last_mut()
+            .expect("declaration outside of any scope")
+            .insert(tag.to_owned(), declared.clone());
+        declared
+    }
+
+    /// The type a struct, union or enum type `ty`, named by its tag in the
+    /// source, stands for: the one its tag is declared with in scope. A tag that is
+    /// not in scope is declared in the current one by its first use, as an
+    /// incomplete type, which is how `struct node *next;` may refer to the
+    /// struct being defined.
+    pub(super) fn resolve_tag(&self, ty: &Type) -> Result<Type> {
+        let tag = tag_of(ty);
+        match self.lookup_tag(tag) {
+            Some(declared) if keyword_of(&declared) == keyword_of(ty) => Ok(declared),
+            Some(_) => Err(mismatched_tag(tag)),
+            None => Ok(self.declare_tag(tag, ty)),
+        }
+    }
+
+    /// The type a definition of `ty`, named by its tag in the source, gives
+    /// a body to: the one its tag names in the current scope, if it was only
+    /// used so far, or a new one.
+    pub(super) fn tag_to_define(&self, ty: &Type, span: Span) -> Result<Type> {
+        let tag = tag_of(ty);
+        let declared = self
+            .tags
+            .borrow()
+            .last()
+            .and_then(|scope| scope.get(tag).cloned());
+        let Some(declared) = declared else {
+            return Ok(self.declare_tag(tag, ty));
+        };
+        let definition = match &declared {
+            Type::Enum(name) => self.enums.borrow().get(name).copied(),
+            declared => self.struct_entry(declared).map(|entry| entry.span),
+        };
+        let error = match (keyword_of(&declared) == keyword_of(ty), definition) {
+            (true, None) => return Ok(declared),
+            (true, Some(_)) => {
+                CodeGenError::new(format!("redefinition of `{} {}`", keyword_of(ty), tag))
+            }
+            (false, _) => mismatched_tag(tag),
+        }
+        .or_span(span);
+        Err(match definition {
+            Some(previous) => error.with_secondary(previous, "previous definition is here"),
+            None => error,
+        })
+    }
+
+    /// Adds the struct or union type of `definition` to the module. The
+    /// members of a struct are laid out in order, each at the next offset
+    /// suited to its alignment, which is how LLVM lays out a non-packed
+    /// struct type and how C does. Those of a union all start at offset 0.
+    pub(super) fn define_struct(&self, definition: &StructDef) -> Result<()> {
+        let keyword = definition.kind.keyword();
+        let ty = self.tag_to_define(
+            &definition.kind.named(definition.tag.clone()),
+            definition.span,
+        )?;
+        let name = tag_of(&ty);
+
+        let mut names = HashSet::new();
+        let mut members = Vec::new();
+        for member in &definition.members {
+            if !names.insert(&member.name) {
+                return Err(CodeGenError::at(
+                    member.span,
+                    format!("duplicate member `{}`", member.name),
+                ));
+            }
+            let ty = self
+                .resolve_type(&member.member_type)
+                .map_err(|e| e.or_span(member.span))?;
+            // a struct is incomplete up to the end of its definition, so
+            // only a pointer may refer to the struct itself
+            if !self.is_complete(&ty) {
+                return Err(CodeGenError::at(
+                    member.span,
+                    format!(
+                        "member `{}` has incomplete type `{}`",
+                        member.name, member.member_type
+                    ),
+                ));
+            }
+            members.push((member.name.clone(), ty));
+        }
+
+        let llvm_type = self
+            .context
+            .opaque_struct_type(&format!("{}.{}", keyword, name));
+        let body = match definition.kind {
+            StructKind::Struct => members
+                .iter()
+                .map(|(_, ty)| ty.into_llvm_type(self))
+                .collect(),
+            StructKind::Union => self.union_body(&members),
+        };
+        llvm_type.set_body(&body, false);
+
+        match self.structs.borrow_mut().entry(name.to_owned()) {
+            Entry::Occupied(_) => unreachable!("redefinition checked above"),
+            Entry::Vacant(slot) => slot.insert(StructEntry {
+                kind: definition.kind,
+                llvm_type,
+                members,
+                span: definition.span,
+            }),
+        };
+        Ok(())
+    }
+
+    /// The LLVM body of a union, which holds its most strictly aligned
+    /// member, the largest one among equally aligned ones, padded with bytes
+    /// to the size of the union.
+    fn union_body(&self, members: &[(String, Type)]) -> Vec<BasicTypeEnum<'ctx>> {
+        let (size, _) = self.layout_members(StructKind::Union, members.iter().map(|(_, ty)| ty));
+        let widest = members
+            .iter()
+            .map(|(_, ty)| (self.layout(ty), ty))
+            .max_by_key(|&((size, align), _)| (align, size))
+            .expect("unions have at least one member");
+        let ((widest_size, _), widest_type) = widest;
+        let mut body = vec![widest_type.into_llvm_type(self)];
+        if size > widest_size {
+            let padding = self
+                .context
+                .i8_type()
+                .array_type((size - widest_size) as u32);
+            body.push(padding.into());
+        }
+        body
+    }
+
+    /// The definition of the struct or union type `ty`, if it is one that is
+    /// defined, with the kind it is named with.
+    pub(super) fn struct_entry(&self, ty: &Type) -> Option<StructEntry<'ctx>> {
+        let (Type::Struct(tag) | Type::Union(tag)) = ty else {
+            return None;
+        };
+        self.structs
+            .borrow()
+            .get(tag)
+            .filter(|entry| entry.kind.named(tag.clone()) == *ty)
+            .cloned()
+    }
+
+    fn lookup_struct(&self, ty: &Type) -> Result<StructEntry<'ctx>> {
+        if !ty.is_struct_or_union() {
+            return Err(CodeGenError::new(format!(
+                "member reference base type `{}` is not a structure or union",
+                ty
+            )));
+        }
+        self.struct_entry(ty)
+            .ok_or_else(|| CodeGenError::new(format!("incomplete definition of type `{}`", ty)))
+    }
+
+    /// The member `name` of the struct or union stored at `object`.
+    pub(super) fn build_member(
+        &self,
+        object: Variable<'ctx>,
+        name: &str,
+    ) -> Result<Variable<'ctx>> {
+        let entry = self.lookup_struct(&object.ty)?;
+        let Some(index) = entry.members.iter().position(|(member, _)| member == name) else {
+            return Err(CodeGenError::new(format!(
+                "no member named `{}` in `{}`",
+                name, object.ty
+            )));
+        };
+        let ptr = match entry.kind {
+            StructKind::Struct => {
+                self.builder
+                    .build_struct_gep(entry.llvm_type, object.ptr, index as u32, name)?
+            }
+            StructKind::Union => object.ptr,
+        };
+        Ok(Variable {
+            ptr,
+            ty: entry.members[index].1.clone(),
+        })
+    }
+
+    /// Size and alignment of a complete type in bytes, under the LP64 model
+    /// that `Type` follows.
+    pub(super) fn layout(&self, ty: &Type) -> (u64, u64) {
+        match ty {
+            Type::Char | Type::UnsignedChar => (1, 1),
+            Type::Int | Type::UnsignedInt | Type::Enum(_) | Type::Float => (4, 4),
+            Type::Long
+            | Type::UnsignedLong
+            | Type::LongLong
+            | Type::UnsignedLongLong
+            | Type::Pointer(_) => (8, 8),
+            Type::Array(element, len) => {
+                let (size, align) = self.layout(element);
+                (size * *len as u64, align)
+            }
+            Type::Struct(_) | Type::Union(_) => {
+                let entry = self
+                    .lookup_struct(ty)
+                    .expect("layout of an incomplete struct");
+                self.layout_members(entry.kind, entry.members.iter().map(|(_, ty)| ty))
+            }
+            Type::Void => unreachable!("`void` has no layout"),
+            Type::Typedef(_) => unreachable!("typedef names are resolved before lowering"),
+        }
+    }
+
+    fn layout_members<'a>(
+        &self,
+        kind: StructKind,
+        members: impl Iterator<Item = &'a Type>,
+    ) -> (u64, u64) {
+        let (mut size, mut align): (u64, u64) = (0, 1);
+        for member in members {
+            let (member_size, member_align) = self.layout(member);
+            size = match kind {
+                StructKind::Struct => size.next_multiple_of(member_align) + member_size,
+                StructKind::Union => size.max(member_size),
+            };
+            align = align.max(member_align);
+        }
+        (size.next_multiple_of(align), align)
+    }
+
+    /// The scalars making up an object of type `ty` at `offset`, with their
+    /// offsets, in order: nested structs and arrays are flattened. The
+    /// members of a union overlap, so their scalars may share offsets.
+    pub(super) fn scalars(&self, ty: &Type, offset: u64, out: &mut Vec<(u64, Type)>) {
+        match ty {
+            Type::Array(element, len) => {
+                let (size, _) = self.layout(element);
+                for i in 0..*len as u64 {
+                    self.scalars(element, offset + i * size, out);
+                }
+            }
+            Type::Struct(_) | Type::Union(_) => {
+                let entry = self
+                    .lookup_struct(ty)
+                    .expect("layout of an incomplete struct");
+                let mut member_offset: u64 = 0;
+                for (_, member) in &entry.members {
+                    if entry.kind == StructKind::Union {
+                        self.scalars(member, offset, out);
+                        continue;
+                    }
+                    let (member_size, member_align) = self.layout(member);
+                    member_offset = member_offset.next_multiple_of(member_align);
+                    self.scalars(member, offset + member_offset, out);
+                    member_offset += member_size;
+                }
+            }
+            scalar => out.push((offset, scalar.clone())),
+        }
+    }
+}
+
+/// The tag a struct, union or enum type is named by.
+fn tag_of(ty: &Type) -> &str {
+    match ty {
+        Type::Struct(tag) | Type::Union(tag) | Type::Enum(tag) => tag,
+        _ => unreachable!("only structs, unions and enums have tags"),
+    }
+}
+
+/// The keyword a type named by a tag is written with.
+fn keyword_of(ty: &Type) -> &'static str {
+    match ty {
+        Type::Struct(_) => "struct",
+        Type::Union(_) => "union",
+        Type::Enum(_) => "enum",
+        _ => unreachable!("only structs, unions and enums have tags"),
+    }
+}
+
+/// `ty`, a type named by a tag, named `tag` instead.
+fn renamed(ty: &Type, tag: String) -> Type {
+    match ty {
+        Type::Struct(_) => Type::Struct(tag),
+        Type::Union(_) => Type::Union(tag),
+        Type::Enum(_) => Type::Enum(tag),
+        _ => unreachable!("only structs, unions and enums have tags"),
+    }
+}
+
+fn mismatched_tag(tag: &str) -> CodeGenError {
+    CodeGenError::new(format!(
+        "use of `{}` with tag type that does not match previous declaration",
+        tag
+    ))
+}
@@ -0,0 +1,314 @@
+use std::{
+    fmt::Write,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A secondary location attached to a diagnostic, e.g. the `{` left open by
+/// a missing `}`.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// An error or warning about a source file, rendered with the offending
+/// lines and caret underlines.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Primary location; `None` for problems with the file as a whole.
+    pub span: Option<Span>,
+    /// Text printed next to the primary underline.
+    pub label: Option<String>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message.into())
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message.into())
+    }
+
+    fn new(severity: Severity, message: String) -> Self {
+        Diagnostic {
+            severity,
+            message,
+            span: None,
+            label: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Renders the diagnostic against `source`, the contents of `path`:
+    ///
+    /// ```text
+    /// error: expected `;`, found `}`
+    ///  --> test.c:3:1
+    ///   |
+    /// 1 | int main() {
+    ///   |            - unclosed `{` opened here
+    /// 2 |   return 0
+    /// 3 | }
+    ///   | ^ expected `;`
+    /// ```
+    pub fn render(&self, path: &Path, source: &str, color: bool) -> String {
+        let style = Style { color };
+        let mut out = String::new();
+
+        let (severity, severity_style) = match self.severity {
+            Severity::Error => ("error", Style::ERROR),
+            Severity::Warning => ("warning", Style::WARNING),
+        };
+        let _ = writeln!(
+            out,
+            "{}{}",
+            style.paint(severity_style, severity),
+            style.paint(Style::BOLD, &format!(": {}", self.message))
+        );
+
+        let Some(span) = self.span else {
+            let _ = writeln!(
+                out,
+                " {} {}",
+                style.paint(Style::GUTTER, "-->"),
+                path.display()
+            );
+            self.render_notes(&mut out, &style, 1);
+            return out;
+        };
+
+        let (line, col) = line_col(source, span.start);
+        let mut labels = vec![Underline {
+            span,
+            message: self.label.as_deref().unwrap_or(""),
+            mark: '^',
+            style: severity_style,
+        }];
+        labels.extend(self.secondary.iter().map(|label| Underline {
+            span: label.span,
+            message: &label.message,
+            mark: '-',
+            style: Style::GUTTER,
+        }));
+
+        let lines: Vec<&str> = source.split('\n').collect();
+        let mut shown: Vec<usize> = labels
+            .iter()
+            .map(|label| line_col(source, label.span.start).0)
+            .collect();
+        shown.sort_unstable();
+        shown.dedup();
+        let width = shown.last().unwrap_or(&line).to_string().len();
+        let gutter = |number: &str| style.paint(Style::GUTTER, &format!("{:>width$} |", number));
+
+        let _ = writeln!(
+            out,
+            "{:width$}{} {}:{}:{}",
+            "",
+            style.paint(Style::GUTTER, "-->"),
+            path.display(),
+            line,
+            col
+        );
+        let _ = writeln!(out, "{}", gutter(""));
+
+        let mut previous = None;
+        for &number in &shown {
+            match previous {
+                // show a single skipped line rather than eliding it
+                Some(prev) if number == prev + 2 => {
+                    let _ = writeln!(
+                        out,
+                        "{} {}",
+                        gutter(&(prev + 1).to_string()),
+                        expand_tabs(lines[prev])
+                    );
+                }
+                Some(prev) if number > prev + 2 => {
+                    let _ = writeln!(out, "{}", style.paint(Style::GUTTER, "..."));
+                }
+                _ => {}
+            }
+            previous = Some(number);
+
+            let text = lines.get(number - 1).copied().unwrap_or("");
+            let line_start = span_of_line(&lines, number);
+            let _ = writeln!(out, "{} {}", gutter(&number.to_string()), expand_tabs(text));
+
+            let mut on_line: Vec<&Underline> = labels
+                .iter()
+                .filter(|label| line_col(source, label.span.start).0 == number)
+                .collect();
+            on_line.sort_by_key(|label| label.span.start);
+            for label in on_line {
+                let start = (label.span.start - line_start).min(text.len());
+                let end = (label.span.end - line_start).clamp(start, text.len());
+                let indent = expand_tabs(&text[..start]).chars().count();
+                let length = expand_tabs(&text[start..end]).chars().count().max(1);
+                let mark: String = std::iter::repeat_n(label.mark, length).collect();
+                let underline = if label.message.is_empty() {
+                    mark
+                } else {
+                    format!("{} {}", mark, label.message)
+                };
+                let _ = writeln!(
+                    out,
+                    "{} {:indent$}{}",
+                    gutter(""),
+                    "",
+                    style.paint(label.style, &underline)
+                );
+            }
+        }
+
+        self.render_notes(&mut out, &style, width);
+        out
+    }
+
+    fn render_notes(&self, out: &mut String, style: &Style, width: usize) {
+        for note in &self.notes {
+            let _ = writeln!(
+                out,
+                "{:width$} {} {}",
+                "",
+                style.paint(Style::GUTTER, "="),
+                style.paint(Style::BOLD, &format!("note: {}", note))
+            );
+        }
+    }
+}
+
+/// Prints `diagnostics` for `path` to stderr, in colour when stderr is a
+/// terminal and `NO_COLOR` is unset.
+pub fn emit(diagnostics: &[Diagnostic], path: &Path, source: &str) {
+    let color = io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.render(path, source, color));
+    }
+}
+
+/// One-based line and column of the byte `offset` in `source`.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// Byte offset at which the one-based line `number` starts.
+fn span_of_line(lines: &[&str], number: usize) -> usize {
+    lines[..number - 1].iter().map(|line| line.len() + 1).sum()
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.trim_end_matches('\r').replace('\t', "    ")
+}
+
+struct Underline<'a> {
+    span: Span,
+    message: &'a str,
+    mark: char,
+    style: &'static str,
+}
+
+/// ANSI styling, applied only when writing to a terminal.
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    const ERROR: &'static str = "\x1b[1;31m";
+    const WARNING: &'static str = "\x1b[1;33m";
+    const GUTTER: &'static str = "\x1b[1;34m";
+    const BOLD: &'static str = "\x1b[1m";
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}\x1b[0m", code, text)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+#[test]
+fn test_line_col() {
+    let source = "int main() {\n  return x;\n}\n";
+    assert_eq!(line_col(source, 0), (1, 1));
+    assert_eq!(line_col(source, 22), (2, 10));
+    assert_eq!(line_col(source, source.len()), (4, 1));
+}
+
+#[test]
+fn test_render() {
+    let source = "int main() {\n  return 0\n}\n";
+    let diagnostic = Diagnostic::error("expected `;`, found `}`")
+        .at((24..25).into())
+        .with_label("expected `;`")
+        .with_secondary((11..12).into(), "unclosed `{` opened here");
+
+    let rendered = diagnostic.render(Path::new("test.c"), source, false);
+    assert_eq!(
+        rendered,
+        "\
+error: expected `;`, found `}`
+ --> test.c:3:1
+  |
+1 | int main() {
+  |            - unclosed `{` opened here
+2 |   return 0
+3 | }
+  | ^ expected `;`
+"
+    );
+
+    let warning = Diagnostic::warning("unused").with_note("at file scope");
+    assert_eq!(
+        warning.render(Path::new("test.c"), source, false),
+        "warning: unused\n --> test.c\n  = note: at file scope\n"
+    );
+}
@@ -0,0 +1,33 @@
+use std::ffi::c_char;
+
+use inkwell::module::Module;
+
+use super::{DriverError, target::optimization_level};
+
+/// Signatures of the program's `int main()` and `int main(int, char **)`.
+type MainFunc = unsafe extern "C" fn() -> i32;
+type MainWithArgsFunc = unsafe extern "C" fn(i32, *const *const c_char) -> i32;
+
+/// JIT-compiles `module` and calls its `main`, returning the value it
+/// returns. A `main` taking arguments is passed the name of the module as
+/// its only one, `argv[0]`.
+pub fn run_main(module: &Module, opt_level: u8) -> Result<i32, DriverError> {
+    let execution_engine = module
+        .create_jit_execution_engine(optimization_level(opt_level))
+        .map_err(|e| DriverError(format!("cannot create JIT: {}", e.to_string_lossy())))?;
+
+    // `check_entry_point` has verified that `main` has one of these
+    // signatures
+    let takes_arguments = module
+        .get_function("main")
+        .is_some_and(|main| main.count_params() > 0);
+    if takes_arguments {
+        let main = unsafe { execution_engine.get_function::<MainWithArgsFunc>("main") }
+            .map_err(|e| DriverError(format!("cannot JIT `main`: {:?}", e)))?;
+        let argv = [module.get_name().as_ptr(), std::ptr::null()];
+        return Ok(unsafe { main.call(1, argv.as_ptr()) });
+    }
+    let main = unsafe { execution_engine.get_function::<MainFunc>("main") }
+        .map_err(|e| DriverError(format!("cannot JIT `main`: {:?}", e)))?;
+    Ok(unsafe { main.call() })
+}
@@ -0,0 +1,61 @@
+use std::{fs, path::Path, process::Command};
+
+use inkwell::module::Module;
+
+use super::DriverError;
+
+/// Checks that the program defines `int main()` or `int main(int argc,
+/// char **argv)`, which the C runtime calls.
+pub fn check_entry_point(module: &Module) -> Result<(), DriverError> {
+    let main = module
+        .get_function("main")
+        .filter(|main| main.count_basic_blocks() > 0)
+        .ok_or_else(|| DriverError("undefined reference to `main`".to_owned()))?;
+    let fn_type = main.get_type();
+    let returns_int = fn_type
+        .get_return_type()
+        .is_some_and(|ty| ty == module.get_context().i32_type().into());
+    let params = fn_type.get_param_types();
+    let takes_arguments = matches!(
+        params.as_slice(),
+        [argc, argv] if argc.is_int_type()
+            && argc.into_int_type().get_bit_width() == 32
+            && argv.is_pointer_type()
+    );
+    if !returns_int || !(params.is_empty() || takes_arguments) {
+        return Err(DriverError(
+            "`main` must be declared as `int main()` or `int main(int, char **)`".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Links `object` into an executable at `output` using the system C compiler
+/// driver (`$CC`, or `cc`), which supplies the C runtime and libc.
+pub fn link_executable(object: &[u8], output: &Path) -> Result<(), DriverError> {
+    let object_path = std::env::temp_dir().join(format!("tinycc-{}.o", std::process::id()));
+    fs::write(&object_path, object)
+        .map_err(|e| DriverError(format!("cannot write `{}`: {}", object_path.display(), e)))?;
+
+    let cc = std::env::var_os("CC").unwrap_or_else(|| "cc".into());
+    let status = Command::new(&cc)
+        .arg(&object_path)
+        .arg("-o")
+        .arg(output)
+        .status();
+    let _ = fs::remove_file(&object_path);
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(DriverError(format!(
+            "linker `{}` failed with {}",
+            cc.to_string_lossy(),
+            status
+        ))),
+        Err(e) => Err(DriverError(format!(
+            "cannot run linker `{}`: {}",
+            cc.to_string_lossy(),
+            e
+        ))),
+    }
+}
@@ -0,0 +1,637 @@
+mod jit;
+mod link;
+mod options;
+mod target;
+
+use std::{fs, io::Write, process::ExitCode};
+
+use inkwell::{
+    context::Context,
+    module::Module,
+    targets::{FileType, TargetMachine},
+};
+
+use crate::{
+    codegen::compile,
+    diagnostics::emit,
+    parser::{lex, parse},
+};
+pub use options::{Emit, Mode, Options, Output, USAGE};
+
+/// Entry point of the `tinycc` binary. Exits with 0 on success, 1 when
+/// compilation fails and 2 on invalid usage.
+pub fn main(args: impl IntoIterator<Item = String>) -> ExitCode {
+    let options = match Options::parse(args) {
+        Ok(Some(options)) => options,
+        Ok(None) => return ExitCode::SUCCESS,
+        Err(msg) => {
+            eprintln!("tinycc: error: {}", msg);
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(status) => ExitCode::from(status),
+        Err(err) => {
+            eprintln!("tinycc: error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Runs the pipeline over every input up to the stage selected by
+/// `--emit`, linking all inputs into a single module for the LLVM stages.
+/// In `run` mode the module is JIT-compiled instead, and the returned exit
+/// status is the value returned by `main`.
+pub fn run(options: &Options) -> Result<u8, DriverError> {
+    let sources = options
+        .inputs
+        .iter()
+        .map(|path| {
+            fs::read_to_string(path)
+                .map(|source| (path.as_path(), source))
+                .map_err(|e| DriverError(format!("cannot read `{}`: {}", path.display(), e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if options.emit == Emit::Tokens {
+        let mut out = String::new();
+        let mut errors = 0;
+        for (path, source) in &sources {
+            let (tokens, diagnostics) = lex(source);
+            for (token, span) in tokens {
+                out.push_str(&format!("{}..{}\t{}\n", span.start, span.end, token));
+            }
+            errors += diagnostics.len();
+            emit(&diagnostics, path, source);
+        }
+        write_output(options, out.as_bytes())?;
+        return match errors {
+            0 => Ok(0),
+            _ => Err(DriverError::previous_errors(errors)),
+        };
+    }
+
+    let mut asts = Vec::new();
+    let mut errors = 0;
+    for (path, source) in &sources {
+        match parse(source) {
+            Ok(ast) => asts.push(ast),
+            Err(diagnostics) => {
+                errors += diagnostics.len();
+                emit(&diagnostics, path, source);
+            }
+        }
+    }
+    if errors > 0 {
+        return Err(DriverError::previous_errors(errors));
+    }
+
+    match options.emit {
+        Emit::Ast => {
+            let out: String = asts.iter().map(|ast| format!("{:#?}\n", ast)).collect();
+            write_output(options, out.as_bytes())?;
+            return Ok(0);
+        }
+        Emit::AstPretty => {
+            let out: String = asts.iter().map(|ast| ast.to_string()).collect();
+            write_output(options, out.as_bytes())?;
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    let context = Context::create();
+    let machine = target::machine(options.target.as_deref(), options.opt_level)?;
+    let mut modules = Vec::new();
+    for ((path, source), ast) in sources.iter().zip(&asts) {
+        let module_name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut diagnostics = Vec::new();
+        let module = compile(
+            &context,
+            ast,
+            &module_name,
+            &machine.get_triple(),
+            &mut diagnostics,
+        );
+        errors += diagnostics.iter().filter(|d| d.is_error()).count();
+        emit(&diagnostics, path, source);
+        modules.extend(module);
+    }
+    if errors > 0 {
+        return Err(DriverError::previous_errors(errors));
+    }
+    let module = link_modules(modules)?;
+    target::prepare_module(&module, &machine, options.opt_level)?;
+
+    match options.mode {
+        Mode::Run => {
+            link::check_entry_point(&module)?;
+            let status = jit::run_main(&module, options.opt_level)?;
+            // like a process exit status, only the low byte survives
+            Ok(status as u8)
+        }
+        Mode::Compile => {
+            emit_module(options, &machine, &module)?;
+            Ok(0)
+        }
+    }
+}
+
+fn emit_module(
+    options: &Options,
+    machine: &TargetMachine,
+    module: &Module,
+) -> Result<(), DriverError> {
+    match options.emit {
+        Emit::Tokens | Emit::Ast | Emit::AstPretty => unreachable!(),
+        Emit::LlvmIr => write_output(options, module.print_to_string().to_bytes()),
+        Emit::Bitcode => write_output(options, module.write_bitcode_to_memory().as_slice()),
+        Emit::Asm => write_output(
+            options,
+            target::write_to_memory(machine, module, FileType::Assembly)?.as_slice(),
+        ),
+        Emit::Obj => write_output(
+            options,
+            target::write_to_memory(machine, module, FileType::Object)?.as_slice(),
+        ),
+        Emit::Exe => {
+            link::check_entry_point(module)?;
+            let object = target::write_to_memory(machine, module, FileType::Object)?;
+            let output = match &options.output {
+                Some(Output::File(path)) => path.clone(),
+                Some(Output::Stdout) => {
+                    return Err(DriverError(
+                        "cannot write an executable to stdout".to_owned(),
+                    ));
+                }
+                None => options.emit.default_output(&options.inputs[0]).unwrap(),
+            };
+            link::link_executable(object.as_slice(), &output)
+        }
+    }
+}
+
+fn link_modules(modules: Vec<Module<'_>>) -> Result<Module<'_>, DriverError> {
+    let mut modules = modules.into_iter();
+    let module = modules.next().expect("at least one input");
+    for other in modules {
+        module
+            .link_in_module(other)
+            .map_err(|e| DriverError(format!("linking failed: {}", e.to_string_lossy())))?;
+    }
+    Ok(module)
+}
+
+/// Writes `bytes` to the `-o` destination. Without `-o`, binary artefacts are
+/// named after the first input and textual dumps go to stdout.
+fn write_output(options: &Options, bytes: &[u8]) -> Result<(), DriverError> {
+    let default_output = options.emit.default_output(&options.inputs[0]);
+    let path = match (&options.output, default_output) {
+        (Some(Output::File(path)), _) => path.clone(),
+        (None, Some(path)) => path,
+        (Some(Output::Stdout), _) | (None, None) => {
+            return std::io::stdout()
+                .write_all(bytes)
+                .map_err(|e| DriverError(format!("cannot write to stdout: {}", e)));
+        }
+    };
+    fs::write(&path, bytes)
+        .map_err(|e| DriverError(format!("cannot write `{}`: {}", path.display(), e)))
+}
+
+#[derive(Debug, Clone)]
+pub struct DriverError(String);
+
+impl DriverError {
+    /// Reported after the diagnostics themselves have been printed.
+    fn previous_errors(count: usize) -> Self {
+        let plural = if count == 1 { "" } else { "s" };
+        DriverError(format!(
+            "aborting due to {} previous error{}",
+            count, plural
+        ))
+    }
+}
+
+impl std::fmt::Display for DriverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for DriverError {}
+
+#[test]
+fn test_executable() {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("answer.c");
+    let exe = dir.join("answer");
+    fs::write(
+        &source,
+        "int twice(int x) { return x * 2; }\nint main() { return twice(21); }\n",
+    )
+    .unwrap();
+
+    let options = Options::parse([
+        source.to_string_lossy().into_owned(),
+        "-o".to_owned(),
+        exe.to_string_lossy().into_owned(),
+    ])
+    .unwrap()
+    .unwrap();
+    run(&options).unwrap();
+
+    let status = std::process::Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_main_with_arguments() {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-argv-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("args.c");
+    let exe = dir.join("args");
+    let program = "int main(int argc, char **argv) {
+           return argc + 40 + (argv[0] != 0) + (argv[argc] == 0) - 1;
+         }
+";
+    fs::write(&source, program).unwrap();
+
+    let options = Options::parse([
+        source.to_string_lossy().into_owned(),
+        "-o".to_owned(),
+        exe.to_string_lossy().into_owned(),
+    ])
+    .unwrap()
+    .unwrap();
+    run(&options).unwrap();
+
+    let status = std::process::Command::new(&exe)
+        .arg("extra")
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(43));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(run_source("arguments", program), 42);
+}
+
+#[test]
+fn test_emit() {
+    use std::path::{Path, PathBuf};
+
+    let dir = std::env::temp_dir().join(format!("tinycc-test-emit-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("zero.c");
+    let input = "int main() { return 0; }\n";
+    fs::write(&source, input).unwrap();
+
+    let emit_to_file = |kind: &str| {
+        let output = dir.join(kind);
+        let options = Options::parse([
+            source.to_string_lossy().into_owned(),
+            format!("--emit={}", kind),
+            "-o".to_owned(),
+            output.to_string_lossy().into_owned(),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(run(&options).unwrap(), 0);
+        fs::read(&output).unwrap()
+    };
+
+    let tokens = String::from_utf8(emit_to_file("tokens")).unwrap();
+    assert_eq!(
+        tokens.lines().take(4).collect::<Vec<_>>(),
+        ["0..3\tint", "4..8\tIdentifier(main)", "8..9\t(", "9..10\t)"]
+    );
+    let pretty = String::from_utf8(emit_to_file("ast-pretty")).unwrap();
+    assert_eq!(pretty, parse(input).unwrap().to_string());
+    let ir = String::from_utf8(emit_to_file("llvm-ir")).unwrap();
+    assert!(ir.contains("define i32 @main()"), "{}", ir);
+    assert!(emit_to_file("bitcode").starts_with(b"BC\xc0\xde"));
+    let asm = String::from_utf8(emit_to_file("asm")).unwrap();
+    assert!(asm.contains("main:"), "{}", asm);
+    assert!(!emit_to_file("obj").is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+
+    // without `-o`, intermediate artefacts are named after the input
+    let input = Path::new("src/zero.c");
+    assert_eq!(Emit::Tokens.default_output(input), None);
+    assert_eq!(
+        Emit::LlvmIr.default_output(input),
+        Some(PathBuf::from("zero.ll"))
+    );
+    assert_eq!(
+        Emit::Obj.default_output(input),
+        Some(PathBuf::from("zero.o"))
+    );
+    assert_eq!(
+        Emit::Exe.default_output(input),
+        Some(PathBuf::from("a.out"))
+    );
+}
+
+#[test]
+fn test_libc_calls() {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-libc-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("hello.c");
+    let exe = dir.join("hello");
+    // there are no string literals yet, so the strings are spelled out
+    fs::write(
+        &source,
+        "int puts(char *s);
+         int printf(char *format, ...);
+         int main() {
+           char hi[3];
+           char format[9];
+           hi[0] = 104; hi[1] = 105; hi[2] = 0;
+           format[0] = 37; format[1] = 100; format[2] = 32;
+           format[3] = 37; format[4] = 46; format[5] = 49; format[6] = 102;
+           format[7] = 10; format[8] = 0;
+           puts(hi);
+           printf(format, hi[1], 2.5);
+           return 0;
+         }
+",
+    )
+    .unwrap();
+
+    let options = Options::parse([
+        source.to_string_lossy().into_owned(),
+        "-o".to_owned(),
+        exe.to_string_lossy().into_owned(),
+    ])
+    .unwrap()
+    .unwrap();
+    run(&options).unwrap();
+
+    let output = std::process::Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    // "%d %.1f\n", with the `char` promoted to `int` and the `float` to
+    // `double`
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n105 2.5\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_run() {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-run-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("answer.c");
+    fs::write(
+        &source,
+        "int twice(int x) { return x * 2; }\nint main() { return twice(21); }\n",
+    )
+    .unwrap();
+
+    let options = Options::parse(["run".to_owned(), source.to_string_lossy().into_owned()])
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.mode, Mode::Run);
+    assert_eq!(run(&options).unwrap(), 42);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Runs `source` in `run` mode and returns the status of `main`.
+#[cfg(test)]
+fn run_source(name: &str, source: &str) -> u8 {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.c", name));
+    fs::write(&path, source).unwrap();
+
+    let options = Options::parse(["run".to_owned(), path.to_string_lossy().into_owned()])
+        .unwrap()
+        .unwrap();
+    let status = run(&options).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    status
+}
+
+#[test]
+fn test_run_arithmetic() {
+    // each check returns its own status, so a failure names the check
+    let source = "int add(int x, int y) { return x + y; }
+         int main() {
+           int a = 7;
+           int b = -3;
+           unsigned u = 0u - 1;
+           long big = 3000000000;
+           float f = 7 / 2;
+           int t = 2.9;
+           char c = 200;
+           if (add(a, b) != 4) return 1;
+           if (a * b != -21) return 2;
+           if (a / b != -2) return 3;
+           if (a % b != 1) return 4;
+           if (-a % 3 != -1) return 5;
+           if (u / 2 != 2147483647u) return 6;
+           if (-1 < 0u) return 7;
+           if (big * 2 != 6000000000) return 8;
+           if (f != 3) return 9;
+           if (t != 2) return 10;
+           if (c != -56) return 11;
+           if (!(a > b && b < 0 || 0)) return 12;
+           if ((a = 2) != 2 || a * a != 4) return 13;
+           return 0;
+         }
+";
+    assert_eq!(run_source("arithmetic", source), 0);
+}
+
+#[test]
+fn test_run_pointers() {
+    let source = "void swap(int *a, int *b) { int t = *a; *a = *b; *b = t; }
+         int *first(int *p) { return p; }
+         int main() {
+           int x = 1;
+           int y = 2;
+           int *px = &x;
+           int **ppx = &px;
+           int *null = 0;
+           void *v = px;
+           int *back = v;
+           swap(px, &y);
+           if (x != 2 || y != 1) return 1;
+           **ppx = 5;
+           if (x != 5) return 2;
+           if (null || !px || null != 0) return 3;
+           if ((px + 1) - px != 1 || px + 1 - 1 != px) return 4;
+           if (back != px || *back != 5) return 5;
+           *first(&y) = *first(px) + 1;
+           if (y != 6) return 6;
+           if (px + 0 != px || px - 0 != px || *(px - 0u) != 5 || 0 + px != px) return 7;
+           return 0;
+         }
+";
+    assert_eq!(run_source("pointers", source), 0);
+}
+
+#[test]
+fn test_run_structs() {
+    let source = "struct point { int x; int y; };
+         struct segment { struct point from; struct point to; };
+         struct point make(int x, int y) { struct point p; p.x = x; p.y = y; return p; }
+         int length(struct segment *s) {
+           return s->to.x - s->from.x + s->to.y - s->from.y;
+         }
+         struct segment flip(struct segment s) {
+           struct point t = s.from;
+           s.from = s.to;
+           s.to = t;
+           return s;
+         }
+         int main() {
+           struct segment s;
+           struct segment f;
+           struct point *p;
+           s.from = make(1, 2);
+           s.to = make(4, 8);
+           if (length(&s) != 9) return 1;
+           f = flip(s);
+           if (f.from.x != 4 || f.to.y != 2) return 2;
+           if (s.from.x != 1 || s.to.y != 8) return 3;
+           p = &f.to;
+           p->x = 7;
+           if (f.to.x != 7 || (*p).y != 2) return 4;
+           if (make(5, 6).y != 6) return 5;
+           return 0;
+         }
+";
+    assert_eq!(run_source("structs", source), 0);
+}
+
+#[test]
+fn test_run_unions() {
+    let source = "enum kind { INT, FLOAT };
+         union value { int i; float f; unsigned char bytes[4]; };
+         struct tagged { enum kind kind; union value value; };
+         struct tagged make_int(int i) {
+           struct tagged t;
+           t.kind = INT;
+           t.value.i = i;
+           return t;
+         }
+         int as_int(struct tagged t) {
+           switch (t.kind) {
+           case INT: return t.value.i;
+           case FLOAT: return t.value.f;
+           }
+           return -1;
+         }
+         int main() {
+           union value v;
+           struct tagged t;
+           v.i = 0x01020304;
+           if (v.bytes[0] + v.bytes[3] != 5) return 1;
+           v.f = 1.0;
+           if (v.i != 0x3F800000) return 2;
+           t = make_int(42);
+           if (as_int(t) != 42) return 3;
+           t.kind = FLOAT;
+           t.value.f = 2.5;
+           if (as_int(t) != 2) return 4;
+           t.kind = 7;
+           if (as_int(t) != -1) return 5;
+           return 0;
+         }
+";
+    assert_eq!(run_source("unions", source), 0);
+}
+
+#[test]
+fn test_run_switch() {
+    let source = "int pick(int x) {
+           switch (x) {
+             case 1: return 1;
+             { case 2: return 2; }
+             if (x) { case 3: x = 30; }
+             return x + 1;
+           }
+           return 0;
+         }
+         int main() {
+           if (pick(1) != 1) return 1;
+           if (pick(2) != 2) return 2;
+           if (pick(3) != 31) return 3;
+           if (pick(4) != 0) return 4;
+           return 0;
+         }
+";
+    assert_eq!(run_source("switch", source), 0);
+}
+
+#[test]
+fn test_struct_abi_with_cc() {
+    let dir = std::env::temp_dir().join(format!("tinycc-test-abi-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = dir.join("structs.c");
+    let object = dir.join("structs.o");
+    let helper = dir.join("helper.c");
+    let exe = dir.join("structs");
+    let declarations = "struct point { int x; float y; };\n\
+                        struct big { long a; long b; long c; };\n";
+    fs::write(
+        &source,
+        format!(
+            "{}struct point make_point(int x, float y);\n\
+             long sum_big(struct big b);\n\
+             long check(void);\n\
+             struct big scale(struct big b, int k) {{\n\
+               b.a = b.a * k; b.b = b.b * k; b.c = b.c * k;\n\
+               return b;\n\
+             }}\n\
+             int main() {{\n\
+               struct point p = make_point(10, 2.0);\n\
+               struct big b;\n\
+               b.a = 6; b.b = 6; b.c = 6;\n\
+               return p.x + p.y + sum_big(b) + check();\n\
+             }}\n",
+            declarations
+        ),
+    )
+    .unwrap();
+    fs::write(
+        &helper,
+        format!(
+            "{}struct big scale(struct big b, int k);\n\
+             struct point make_point(int x, float y) {{ struct point p = {{x, y}}; return p; }}\n\
+             long sum_big(struct big b) {{ return b.a + b.b + b.c; }}\n\
+             long check(void) {{\n\
+               struct big b = {{1, 2, 3}};\n\
+               struct big r = scale(b, 2);\n\
+               return r.a + r.b + r.c;\n\
+             }}\n",
+            declarations
+        ),
+    )
+    .unwrap();
+
+    let options = Options::parse([
+        source.to_string_lossy().into_owned(),
+        "--emit=obj".to_owned(),
+        "-o".to_owned(),
+        object.to_string_lossy().into_owned(),
+    ])
+    .unwrap()
+    .unwrap();
+    run(&options).unwrap();
+
+    let status = std::process::Command::new("cc")
+        .arg(&object)
+        .arg(&helper)
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = std::process::Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+    fs::remove_dir_all(&dir).unwrap();
+}
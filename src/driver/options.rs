@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: tinycc [options] <file>...
+       tinycc run [-O<level>] <file>...
+
+`run` JIT-compiles the program and exits with the value returned by `main`.
+
+Options:
+  -o <file>        Write output to <file> (`-` for stdout)
+  -O<level>        Optimization level 0-3 (`-O` is `-O2`)
+  --emit <kind>    Stop after the given stage and write its output:
+                   tokens, ast, ast-pretty, llvm-ir, bitcode, asm, obj,
+                   exe (the default)
+  --target <triple>
+                   Generate code for <triple> instead of the host
+  -h, --help       Print this help
+";
+
+/// The pipeline stage to stop after, and the artefact it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// The token stream, one token per line.
+    Tokens,
+    /// The raw syntax tree (`Debug` form).
+    Ast,
+    /// The syntax tree as printed by the pretty printer.
+    AstPretty,
+    /// Textual LLVM IR.
+    LlvmIr,
+    /// LLVM bitcode.
+    Bitcode,
+    /// Target assembly.
+    Asm,
+    /// Target object file.
+    Obj,
+    /// Executable linked by the system C compiler driver.
+    Exe,
+}
+
+impl Emit {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(Emit::Tokens),
+            "ast" => Some(Emit::Ast),
+            "ast-pretty" => Some(Emit::AstPretty),
+            "llvm-ir" => Some(Emit::LlvmIr),
+            "bitcode" => Some(Emit::Bitcode),
+            "asm" => Some(Emit::Asm),
+            "obj" => Some(Emit::Obj),
+            "exe" => Some(Emit::Exe),
+            _ => None,
+        }
+    }
+
+    /// Output file used when no `-o` is given: named after `input` for
+    /// intermediate artefacts, `a.out` for executables. `None` means the
+    /// artefact goes to stdout.
+    pub fn default_output(self, input: &Path) -> Option<PathBuf> {
+        let extension = match self {
+            Emit::Tokens | Emit::Ast | Emit::AstPretty => return None,
+            Emit::LlvmIr => "ll",
+            Emit::Bitcode => "bc",
+            Emit::Asm => "s",
+            Emit::Obj => "o",
+            Emit::Exe => return Some(PathBuf::from("a.out")),
+        };
+        Some(Path::new(input.file_name().unwrap_or_default()).with_extension(extension))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Compile to the artefact selected by `--emit`.
+    Compile,
+    /// JIT-compile and call `main`.
+    Run,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Stdout,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub mode: Mode,
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<Output>,
+    pub opt_level: u8,
+    pub emit: Emit,
+    /// Target triple; `None` for the host.
+    pub target: Option<String>,
+}
+
+impl Options {
+    /// Parses command-line arguments (without the program name). Returns
+    /// `Ok(None)` when only help was requested.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut inputs = Vec::new();
+        let mut output = None;
+        let mut opt_level = 0;
+        let mut emit = Emit::Exe;
+        let mut target = None;
+
+        let mut args = args.into_iter().peekable();
+        let mode = match args.peek().map(String::as_str) {
+            Some("run") => {
+                args.next();
+                Mode::Run
+            }
+            _ => Mode::Compile,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    return Ok(None);
+                }
+                "-o" => {
+                    let path = args.next().ok_or("missing file name after `-o`")?;
+                    output = Some(match path.as_str() {
+                        "-" => Output::Stdout,
+                        _ => Output::File(path.into()),
+                    });
+                }
+                "--emit" => {
+                    let kind = args.next().ok_or("missing output kind after `--emit`")?;
+                    emit = Emit::from_name(&kind)
+                        .ok_or_else(|| format!("unknown output kind `{}`", kind))?;
+                }
+                _ if arg.starts_with("--emit=") => {
+                    let kind = &arg["--emit=".len()..];
+                    emit = Emit::from_name(kind)
+                        .ok_or_else(|| format!("unknown output kind `{}`", kind))?;
+                }
+                "--target" => {
+                    target = Some(args.next().ok_or("missing triple after `--target`")?);
+                }
+                _ if arg.starts_with("--target=") => {
+                    target = Some(arg["--target=".len()..].to_owned());
+                }
+                _ if arg.starts_with("-O") => {
+                    opt_level = match &arg[2..] {
+                        "" => 2,
+                        level @ ("0" | "1" | "2" | "3") => level.parse().unwrap(),
+                        _ => return Err(format!("invalid optimization level `{}`", arg)),
+                    };
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option `{}`", arg));
+                }
+                _ => inputs.push(PathBuf::from(arg)),
+            }
+        }
+
+        if inputs.is_empty() {
+            return Err("no input files".to_owned());
+        }
+        if mode == Mode::Run && (output.is_some() || emit != Emit::Exe || target.is_some()) {
+            return Err("`run` does not accept `-o`, `--emit` or `--target`".to_owned());
+        }
+
+        Ok(Some(Options {
+            mode,
+            inputs,
+            output,
+            opt_level,
+            emit,
+            target,
+        }))
+    }
+}
+
+#[test]
+fn test() {
+    let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+    let options = Options::parse(args(&["-O", "a.c", "--emit=llvm-ir", "-o", "-", "b.c"]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.inputs, [PathBuf::from("a.c"), PathBuf::from("b.c")]);
+    assert_eq!(options.output, Some(Output::Stdout));
+    assert_eq!(options.opt_level, 2);
+    assert_eq!(options.emit, Emit::LlvmIr);
+
+    let options = Options::parse(args(&["--emit", "ast-pretty", "a.c"]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.emit, Emit::AstPretty);
+    assert_eq!(options.output, None);
+
+    let options = Options::parse(args(&["a.c", "--target", "aarch64-unknown-linux-gnu"]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.emit, Emit::Exe);
+    assert_eq!(options.target.as_deref(), Some("aarch64-unknown-linux-gnu"));
+
+    assert_eq!(options.mode, Mode::Compile);
+
+    let options = Options::parse(args(&["run", "-O3", "a.c"]))
+        .unwrap()
+        .unwrap();
+    assert_eq!(options.mode, Mode::Run);
+    assert_eq!(options.opt_level, 3);
+    assert_eq!(
+        Options::parse(args(&["run", "a.c", "--emit=obj"])).unwrap_err(),
+        "`run` does not accept `-o`, `--emit` or `--target`"
+    );
+
+    for (arguments, message) in [
+        (&[][..], "no input files"),
+        (&["a.c", "-O7"][..], "invalid optimization level `-O7`"),
+        (&["a.c", "--emit", "pdf"][..], "unknown output kind `pdf`"),
+        (&["a.c", "-o"][..], "missing file name after `-o`"),
+        (
+            &["a.c", "--frobnicate"][..],
+            "unknown option `--frobnicate`",
+        ),
+    ] {
+        assert_eq!(Options::parse(args(arguments)).unwrap_err(), message);
+    }
+}
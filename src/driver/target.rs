@@ -0,0 +1,150 @@
+use inkwell::{
+    OptimizationLevel,
+    memory_buffer::MemoryBuffer,
+    module::Module,
+    passes::PassBuilderOptions,
+    targets::{
+        CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+    },
+};
+
+use super::DriverError;
+
+/// Creates a target machine for `triple`, or for the host (tuned to the host
+/// CPU) when no triple is given.
+pub fn machine(triple: Option<&str>, opt_level: u8) -> Result<TargetMachine, DriverError> {
+    let config = InitializationConfig::default();
+    let (triple, cpu, features) = match triple {
+        None => {
+            Target::initialize_native(&config).map_err(DriverError)?;
+            (
+                TargetMachine::get_default_triple(),
+                TargetMachine::get_host_cpu_name()
+                    .to_string_lossy()
+                    .into_owned(),
+                TargetMachine::get_host_cpu_features()
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+        Some(triple) => {
+            Target::initialize_all(&config);
+            (
+                TargetTriple::create(triple),
+                "generic".to_owned(),
+                String::new(),
+            )
+        }
+    };
+
+    let name = triple.as_str().to_string_lossy().into_owned();
+    if !has_data_model(&name) {
+        return Err(DriverError(format!(
+            "unsupported target `{}`: tinycc needs 64-bit `long` and pointers and a signed `char`",
+            name
+        )));
+    }
+    let target = Target::from_triple(&triple).map_err(|e| {
+        DriverError(format!(
+            "unsupported target `{}`: {}",
+            name,
+            e.to_string_lossy()
+        ))
+    })?;
+    target
+        .create_target_machine(
+            &triple,
+            &cpu,
+            &features,
+            optimization_level(opt_level),
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| DriverError(format!("cannot create a target machine for `{}`", name)))
+}
+
+/// Whether C on `triple` is LP64 with a signed `char`, which `Type` and
+/// struct layout assume. Windows is LLP64, and `char` is unsigned on AArch64
+/// except on Apple's platforms.
+fn has_data_model(triple: &str) -> bool {
+    let arch = triple.split('-').next().unwrap_or_default();
+    match arch {
+        _ if triple.contains("windows") => false,
+        "x86_64" | "amd64" => true,
+        "aarch64" | "arm64" | "arm64e" => triple.contains("apple"),
+        _ => false,
+    }
+}
+
+/// Stamps `module` with the machine's triple and data layout and runs the
+/// standard optimization pipeline for `opt_level`.
+pub fn prepare_module(
+    module: &Module,
+    machine: &TargetMachine,
+    opt_level: u8,
+) -> Result<(), DriverError> {
+    module.set_triple(&machine.get_triple());
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+
+    if opt_level > 0 {
+        module
+            .run_passes(
+                &format!("default<O{}>", opt_level),
+                machine,
+                PassBuilderOptions::create(),
+            )
+            .map_err(|e| DriverError(format!("optimization failed: {}", e.to_string_lossy())))?;
+    }
+    Ok(())
+}
+
+/// Runs the target backend over `module`, producing assembly or an object
+/// file.
+pub fn write_to_memory(
+    machine: &TargetMachine,
+    module: &Module,
+    file_type: FileType,
+) -> Result<MemoryBuffer, DriverError> {
+    machine
+        .write_to_memory_buffer(module, file_type)
+        .map_err(|e| DriverError(format!("code generation failed: {}", e.to_string_lossy())))
+}
+
+pub fn optimization_level(opt_level: u8) -> OptimizationLevel {
+    match opt_level {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Less,
+        2 => OptimizationLevel::Default,
+        _ => OptimizationLevel::Aggressive,
+    }
+}
+
+#[test]
+fn test_data_models() {
+    for triple in [
+        "x86_64-unknown-linux-gnu",
+        "x86_64-apple-darwin",
+        "x86_64-unknown-freebsd",
+        "aarch64-apple-darwin",
+        "arm64-apple-ios",
+    ] {
+        assert!(has_data_model(triple), "{}", triple);
+    }
+    for triple in [
+        "x86_64-pc-windows-msvc",
+        "x86_64-pc-windows-gnu",
+        "aarch64-unknown-linux-gnu",
+        "aarch64-pc-windows-msvc",
+        "i686-unknown-linux-gnu",
+        "riscv64gc-unknown-linux-gnu",
+    ] {
+        assert!(!has_data_model(triple), "{}", triple);
+    }
+
+    let err = machine(Some("aarch64-unknown-linux-gnu"), 0).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unsupported target `aarch64-unknown-linux-gnu`: \
+         tinycc needs 64-bit `long` and pointers and a signed `char`"
+    );
+}
@@ -1,4 +1,6 @@
 mod ast;
 mod codegen;
+mod diagnostics;
+pub mod driver;
 mod parser;
 mod prettyprint;
@@ -1,5 +1,13 @@
-use super::{expr::expr_parser, token::Token};
-use crate::ast::{Block, BlockItem, Declaration, Statement, Type};
+use super::{
+    decl::{
+        TypeDefinition, declaration_parser, enum_declaration, struct_declaration,
+        typedef_declaration,
+    },
+    expr::expr_parser,
+    recovery::skip_statement,
+    token::Token,
+};
+use crate::ast::{Block, BlockItem, Expression, ExpressionKind, Statement, StatementKind};
 use chumsky::{input::ValueInput, prelude::*};
 
 pub fn block_parser<'tokens, 'src: 'tokens, I>()
@@ -10,65 +18,308 @@ where
     let mut statement = Recursive::declare();
     let mut block = Recursive::declare();
 
-    let type_ = select! {
-        Token::Identifier(name) if name == "int" => Type::Int,
-        Token::Identifier(name) if name == "float" => Type::Float,
-        Token::Identifier(name) if name == "void" => Type::Void,
-    };
-
-    let declaration = type_
-        .then(select! {Token::Identifier(name) => name})
-        .then(just(Token::Assign).ignore_then(expr_parser::<I>()).or_not())
-        .then_ignore(just(Token::Semicolon))
-        .map(|((type_, name), expr)| Declaration {
-            var_type: type_,
-            name,
-            init: expr,
-        });
-
+    // a malformed item becomes an error statement, and parsing resumes after
+    // the next `;` or before the `}` closing the block
     let block_item = choice((
-        statement.clone().map(|stmt| BlockItem::Stmt(stmt)),
-        declaration.map(|decl| BlockItem::Decl(decl)),
-    ));
+        statement.clone().map(|stmt| vec![BlockItem::Stmt(stmt)]),
+        declaration_parser().map(|decl| vec![BlockItem::Decl(decl)]),
+        struct_declaration().map(|(definition, decl)| {
+            std::iter::once(BlockItem::Struct(definition))
+                .chain(decl.map(BlockItem::Decl))
+                .collect()
+        }),
+        enum_declaration().map(|(definition, decl)| {
+            std::iter::once(BlockItem::Enum(definition))
+                .chain(decl.map(BlockItem::Decl))
+                .collect()
+        }),
+        typedef_declaration().map(|(definition, typedef)| {
+            definition
+                .map(|definition| match definition {
+                    TypeDefinition::Struct(definition) => BlockItem::Struct(definition),
+                    TypeDefinition::Enum(definition) => BlockItem::Enum(definition),
+                })
+                .into_iter()
+                .chain(std::iter::once(BlockItem::Typedef(typedef)))
+                .collect()
+        }),
+    ))
+    .recover_with(via_parser(skip_statement().map_with(|(), e| {
+        vec![BlockItem::Stmt(Statement {
+            kind: StatementKind::Error,
+            span: e.span(),
+        })]
+    })));
 
     let block_impl = block_item
         .repeated()
-        .collect()
+        .collect::<Vec<_>>()
         .delimited_by(just(Token::LeftBrace), just(Token::RightBrace))
-        .map(|items| Block { items });
+        .map_with(|items, e| Block {
+            items: items.into_iter().flatten().collect(),
+            span: e.span(),
+        })
+        .recover_with(via_parser(nested_delimiters(
+            Token::LeftBrace,
+            Token::RightBrace,
+            [(Token::LeftParen, Token::RightParen)],
+            |span| Block {
+                items: Vec::new(),
+                span,
+            },
+        )));
 
     block.define(block_impl);
 
+    // the parenthesized condition of `if` and `while`
+    let condition = expr_parser::<I>()
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        .recover_with(via_parser(nested_delimiters(
+            Token::LeftParen,
+            Token::RightParen,
+            [(Token::LeftBrace, Token::RightBrace)],
+            |span| Expression {
+                kind: ExpressionKind::Error,
+                span,
+            },
+        )));
+
     let return_stmt = just(Token::Return)
         .ignore_then(expr_parser::<I>().or_not())
         .then_ignore(just(Token::Semicolon))
-        .map(|expr| Statement::Ret(expr));
+        .map(StatementKind::Ret);
 
     let if_stmt = just(Token::If)
-        .ignore_then(
-            expr_parser::<I>().delimited_by(just(Token::LeftParen), just(Token::RightParen)),
-        )
+        .ignore_then(condition.clone())
         .then(statement.clone())
         .then(just(Token::Else).ignore_then(statement.clone()).or_not())
-        .map(|((condition, then_branch), else_branch)| Statement::If {
-            condition,
-            then_branch: Box::new(then_branch),
-            else_branch: else_branch.map(Box::new),
-        });
+        .map(
+            |((condition, then_branch), else_branch)| StatementKind::If {
+                condition,
+                then_branch: Box::new(then_branch),
+                else_branch: else_branch.map(Box::new),
+            },
+        );
 
     let expr_stmt = expr_parser::<I>()
         .then_ignore(just(Token::Semicolon))
-        .map(Statement::Expr);
+        .map(StatementKind::Expr);
 
-    // TODO: while stmt, empty stmt
+    let while_stmt = just(Token::While)
+        .ignore_then(condition.clone())
+        .then(statement.clone())
+        .map(|(condition, body)| StatementKind::While {
+            condition,
+            body: Box::new(body),
+        });
+
+    let switch_stmt = just(Token::Switch)
+        .ignore_then(condition.clone())
+        .then(statement.clone())
+        .map(|(condition, body)| StatementKind::Switch {
+            condition,
+            body: Box::new(body),
+        });
+
+    let case_stmt = just(Token::Case)
+        .ignore_then(expr_parser::<I>())
+        .then_ignore(just(Token::Colon))
+        .then(statement.clone())
+        .map(|(value, body)| StatementKind::Case {
+            value,
+            body: Box::new(body),
+        });
+
+    let default_stmt = just(Token::Default)
+        .ignore_then(just(Token::Colon))
+        .ignore_then(statement.clone())
+        .map(|body| StatementKind::Default(Box::new(body)));
+
+    let break_stmt = just(Token::Break)
+        .then(just(Token::Semicolon))
+        .to(StatementKind::Break);
+
+    let empty_stmt = just(Token::Semicolon).to(StatementKind::Empty);
 
     let statement_impl = choice((
-        block.clone().map(Statement::Block),
+        block.clone().map(StatementKind::Block),
         return_stmt,
         if_stmt,
+        while_stmt,
+        switch_stmt,
+        case_stmt,
+        default_stmt,
+        break_stmt,
+        empty_stmt,
         expr_stmt,
-    ));
+    ))
+    .map_with(|kind, e| Statement {
+        kind,
+        span: e.span(),
+    });
     statement.define(statement_impl);
 
     block
 }
+
+#[test]
+fn test_while_and_empty() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = r#"
+        int main() {
+          int i = 0;
+          while (i < 10) {
+            while (i < 5) i = i + 1;
+            ;
+          }
+          ;
+          return i;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+    let Item::Function(main) = &ast.program[0] else {
+        panic!("expected a function");
+    };
+    let items = &main.body.as_ref().unwrap().items;
+
+    let BlockItem::Stmt(Statement {
+        kind: StatementKind::While { body, .. },
+        ..
+    }) = &items[1]
+    else {
+        panic!("expected a while statement");
+    };
+    let StatementKind::Block(inner) = &body.kind else {
+        panic!("expected a block as the loop body");
+    };
+    assert!(matches!(
+        &inner.items[0],
+        BlockItem::Stmt(Statement {
+            kind: StatementKind::While { .. },
+            ..
+        })
+    ));
+    assert!(matches!(
+        &inner.items[1],
+        BlockItem::Stmt(Statement {
+            kind: StatementKind::Empty,
+            ..
+        })
+    ));
+    let BlockItem::Stmt(empty) = &items[2] else {
+        panic!("expected a statement");
+    };
+    assert!(matches!(empty.kind, StatementKind::Empty));
+    assert_eq!(&input[empty.span.start..empty.span.end], ";");
+}
+
+#[test]
+fn test_switch() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = r#"
+        int main() {
+          switch (1) {
+            case 1: case 2: return 0;
+            default: break;
+          }
+          return 1;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+    let Item::Function(main) = &ast.program[0] else {
+        panic!("expected a function");
+    };
+    let items = &main.body.as_ref().unwrap().items;
+
+    let BlockItem::Stmt(Statement {
+        kind: StatementKind::Switch { body, .. },
+        ..
+    }) = &items[0]
+    else {
+        panic!("expected a switch statement");
+    };
+    let StatementKind::Block(body) = &body.kind else {
+        panic!("expected a block as the switch body");
+    };
+    let BlockItem::Stmt(case) = &body.items[0] else {
+        panic!("expected a statement");
+    };
+    let StatementKind::Case { body: inner, .. } = &case.kind else {
+        panic!("expected a case label");
+    };
+    assert!(matches!(inner.kind, StatementKind::Case { .. }));
+    assert_eq!(
+        &input[case.span.start..case.span.end],
+        "case 1: case 2: return 0;"
+    );
+    let BlockItem::Stmt(Statement {
+        kind: StatementKind::Default(default),
+        ..
+    }) = &body.items[1]
+    else {
+        panic!("expected a default label");
+    };
+    assert!(matches!(default.kind, StatementKind::Break));
+}
+
+#[test]
+fn test_dangling_else() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = r#"
+        int main() {
+          int x;
+          if (1) if (0) x = 1; else x = 2;
+          while (x) if (x) x = 0; else ;
+          return x;
+        }
+    "#;
+    let ast = parse(input).unwrap();
+    let Item::Function(main) = &ast.program[0] else {
+        panic!("expected a function");
+    };
+    let items = &main.body.as_ref().unwrap().items;
+
+    // the `else` belongs to the innermost `if`
+    let BlockItem::Stmt(Statement {
+        kind:
+            StatementKind::If {
+                then_branch,
+                else_branch: None,
+                ..
+            },
+        ..
+    }) = &items[1]
+    else {
+        panic!("expected an if statement without else");
+    };
+    assert!(matches!(
+        then_branch.kind,
+        StatementKind::If {
+            else_branch: Some(_),
+            ..
+        }
+    ));
+    assert_eq!(
+        &input[then_branch.span.start..then_branch.span.end],
+        "if (0) x = 1; else x = 2;"
+    );
+
+    let BlockItem::Stmt(Statement {
+        kind: StatementKind::While { body, .. },
+        ..
+    }) = &items[2]
+    else {
+        panic!("expected a while statement");
+    };
+    let StatementKind::If {
+        else_branch: Some(else_branch),
+        ..
+    } = &body.kind
+    else {
+        panic!("expected an if/else as the loop body");
+    };
+    assert!(matches!(else_branch.kind, StatementKind::Empty));
+}
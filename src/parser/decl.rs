@@ -0,0 +1,662 @@
+use super::{expr::expr_parser, literal::int_literal, token::Token};
+use crate::ast::{
+    Declaration, EnumDef, Enumerator, Expression, Literal, Member, StructDef, StructKind, Type,
+    Typedef,
+};
+use chumsky::{input::ValueInput, prelude::*};
+
+/// A keyword naming all or part of a basic type, as in `unsigned long int`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Specifier {
+    Void,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Signed,
+    Unsigned,
+}
+
+impl Specifier {
+    fn name(self) -> &'static str {
+        match self {
+            Specifier::Void => "void",
+            Specifier::Char => "char",
+            Specifier::Short => "short",
+            Specifier::Int => "int",
+            Specifier::Long => "long",
+            Specifier::Float => "float",
+            Specifier::Double => "double",
+            Specifier::Signed => "signed",
+            Specifier::Unsigned => "unsigned",
+        }
+    }
+
+    /// Whether the two may appear in the same declaration (C11 6.7.2).
+    fn combines_with(self, other: Specifier) -> bool {
+        use Specifier::*;
+        let pair = |a, b| {
+            matches!(
+                (a, b),
+                (Int, Signed | Unsigned | Short | Long)
+                    | (Char | Short | Long, Signed | Unsigned)
+                    | (Long, Long | Double)
+            )
+        };
+        pair(self, other) || pair(other, self)
+    }
+}
+
+/// The type named by the specifiers of one declaration, or the first
+/// specifier that does not fit with those before it and why.
+fn resolve(specifiers: &[(Specifier, SimpleSpan)]) -> Result<Type, (String, SimpleSpan)> {
+    for (i, &(specifier, span)) in specifiers.iter().enumerate() {
+        let before = &specifiers[..i];
+        if let Some(&(previous, _)) = before.iter().find(|(p, _)| !p.combines_with(specifier)) {
+            let msg = if previous == specifier {
+                format!("duplicate `{}`", specifier.name())
+            } else {
+                format!(
+                    "cannot combine `{}` with previous `{}`",
+                    specifier.name(),
+                    previous.name()
+                )
+            };
+            return Err((msg, span));
+        }
+        if specifier == Specifier::Long
+            && before.iter().filter(|(p, _)| *p == Specifier::Long).count() == 2
+        {
+            return Err(("`long long long` is too long".to_owned(), span));
+        }
+    }
+
+    let find = |wanted| specifiers.iter().find(|(s, _)| *s == wanted);
+    for unsupported in [Specifier::Short, Specifier::Double] {
+        if let Some(&(specifier, span)) = find(unsupported) {
+            return Err((
+                format!("type `{}` is not supported", specifier.name()),
+                span,
+            ));
+        }
+    }
+    if find(Specifier::Void).is_some() {
+        return Ok(Type::Void);
+    }
+    if find(Specifier::Float).is_some() {
+        return Ok(Type::Float);
+    }
+    let unsigned = find(Specifier::Unsigned).is_some();
+    if find(Specifier::Char).is_some() {
+        return Ok(if unsigned {
+            Type::UnsignedChar
+        } else {
+            Type::Char
+        });
+    }
+    let longs = specifiers
+        .iter()
+        .filter(|(s, _)| *s == Specifier::Long)
+        .count();
+    Ok(match (unsigned, longs) {
+        (false, 0) => Type::Int,
+        (true, 0) => Type::UnsignedInt,
+        (false, 1) => Type::Long,
+        (true, 1) => Type::UnsignedLong,
+        (false, _) => Type::LongLong,
+        (true, _) => Type::UnsignedLongLong,
+    })
+}
+
+/// The type specifiers at the start of a declaration, e.g. `int`,
+/// `unsigned long`, `struct point`, `enum color` or a typedef name.
+/// Specifiers that cannot be combined are reported, and the declaration is
+/// read as an `int` one.
+pub fn type_specifier<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, Type, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let struct_type = struct_kind()
+        .then(identifier())
+        .map(|(kind, tag)| kind.named(tag));
+    let enum_type = just(Token::Enum).ignore_then(identifier()).map(Type::Enum);
+    let typedef_name = select! { Token::TypeName(name) => Type::Typedef(name) };
+
+    let basic_type = select! {
+        Token::Void => Specifier::Void,
+        Token::Char => Specifier::Char,
+        Token::Short => Specifier::Short,
+        Token::Int => Specifier::Int,
+        Token::Long => Specifier::Long,
+        Token::Float => Specifier::Float,
+        Token::Double => Specifier::Double,
+        Token::Signed => Specifier::Signed,
+        Token::Unsigned => Specifier::Unsigned,
+    }
+    .map_with(|specifier, e| (specifier, e.span()))
+    .repeated()
+    .at_least(1)
+    .collect::<Vec<_>>()
+    .validate(|specifiers, _, emitter| {
+        resolve(&specifiers).unwrap_or_else(|(msg, span)| {
+            emitter.emit(Rich::custom(span, msg));
+            Type::Int
+        })
+    })
+    .labelled("type");
+
+    choice((struct_type, enum_type, typedef_name, basic_type))
+}
+
+/// The `struct` or `union` keyword.
+fn struct_kind<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, StructKind, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    select! {
+        Token::Struct => StructKind::Struct,
+        Token::Union => StructKind::Union,
+    }
+}
+
+/// A type specifier followed by the `*`s of a pointer declarator, e.g.
+/// `int **`.
+pub fn declared_type<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, Type, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    type_specifier().foldl(just(Token::Asterisk).repeated(), |ty, _| {
+        Type::Pointer(Box::new(ty))
+    })
+}
+
+/// The `[N]` of an array declarator. The size must be an integer literal
+/// greater than zero; a bad size is reported and read as 1.
+pub fn array_size<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, u32, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    select! { Token::IntLiteral(text) => text }
+        .validate(|text, e, emitter| {
+            let size = match int_literal(&text) {
+                Ok(Literal::Int { value: 0, .. }) => {
+                    Err("array size must be greater than zero".to_owned())
+                }
+                Ok(Literal::Int { value, .. }) => {
+                    u32::try_from(value).map_err(|_| format!("array size `{}` is too large", text))
+                }
+                Ok(Literal::Float(_)) => unreachable!("integer literal"),
+                Err(msg) => Err(msg),
+            };
+            size.unwrap_or_else(|msg| {
+                emitter.emit(Rich::custom(e.span(), msg));
+                1
+            })
+        })
+        .labelled("array size")
+        .delimited_by(just(Token::LeftBracket), just(Token::RightBracket))
+}
+
+/// `ty` with the array dimensions of a declarator applied, outermost first:
+/// `int m[2][3]` declares an array of 2 arrays of 3 `int`s.
+pub fn array_of(ty: Type, dimensions: Vec<u32>) -> Type {
+    dimensions
+        .into_iter()
+        .rev()
+        .fold(ty, |ty, len| Type::Array(Box::new(ty), len))
+}
+
+/// The name declared by a declaration. A keyword in its place is reported
+/// and taken as the name, so that the rest of the declaration still parses.
+pub fn identifier<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, String, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let keyword =
+        select! { token if token.keyword().is_some() => token }.validate(|token, e, emitter| {
+            emitter.emit(Rich::custom(
+                e.span(),
+                format!("expected identifier, found keyword `{}`", token),
+            ));
+            token.to_string()
+        });
+
+    select! { Token::Identifier(name) => name }
+        .or(keyword)
+        .labelled("identifier")
+}
+
+/// What follows the type specifier in a variable declaration: the `*`s of a
+/// pointer, the name, array dimensions and the initializer.
+#[derive(Clone)]
+struct InitDeclarator {
+    pointers: usize,
+    name: String,
+    dimensions: Vec<u32>,
+    init: Option<Expression>,
+}
+
+impl InitDeclarator {
+    fn declare(self, ty: Type, span: SimpleSpan) -> Declaration {
+        let ty = (0..self.pointers).fold(ty, |ty, _| Type::Pointer(Box::new(ty)));
+        Declaration {
+            var_type: array_of(ty, self.dimensions),
+            name: self.name,
+            init: self.init,
+            span,
+        }
+    }
+}
+
+fn init_declarator<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, InitDeclarator, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    just(Token::Asterisk)
+        .repeated()
+        .count()
+        .then(identifier())
+        .then(array_size().repeated().collect::<Vec<_>>())
+        .then(just(Token::Assign).ignore_then(expr_parser::<I>()).or_not())
+        .then_ignore(just(Token::Semicolon))
+        .map(|(((pointers, name), dimensions), init)| InitDeclarator {
+            pointers,
+            name,
+            dimensions,
+            init,
+        })
+}
+
+/// A variable declaration such as `int x = 1;` or `float m[4][4];`, in a
+/// block or at file scope.
+pub fn declaration_parser<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, Declaration, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    type_specifier()
+        .then(init_declarator())
+        .map_with(|(ty, declarator), e| declarator.declare(ty, e.span()))
+}
+
+/// The specifier of a struct or union type along with its definition, e.g.
+/// `struct point { int x; float y; }`. A struct defined without a tag is
+/// given one that no identifier can clash with.
+fn struct_definition<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, StructDef, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let member = declared_type()
+        .then(identifier())
+        .then(array_size().repeated().collect::<Vec<_>>())
+        .then_ignore(just(Token::Semicolon))
+        .map_with(|((ty, name), dimensions), e| Member {
+            name,
+            member_type: array_of(ty, dimensions),
+            span: e.span(),
+        });
+
+    struct_kind()
+        .then(identifier().or_not())
+        .then(
+            member
+                .repeated()
+                .at_least(1)
+                .collect()
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .map_with(|((kind, tag), members), e| {
+            let span: SimpleSpan = e.span();
+            StructDef {
+                kind,
+                tag: tag.unwrap_or_else(|| format!("(anonymous at {})", span.start)),
+                members,
+                span,
+            }
+        })
+}
+
+/// A struct or union definition such as `struct point { int x; float y; };`,
+/// which may also declare a variable of the new type:
+/// `struct point { ... } origin;`.
+pub fn struct_declaration<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (StructDef, Option<Declaration>), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    struct_definition()
+        .then(choice((
+            just(Token::Semicolon).to(None),
+            init_declarator().map(Some),
+        )))
+        .map_with(|(definition, declarator), e| {
+            let declaration = declarator.map(|declarator| {
+                declarator.declare(definition.kind.named(definition.tag.clone()), e.span())
+            });
+            (definition, declaration)
+        })
+}
+
+/// The specifier of an enumerated type along with its definition, e.g.
+/// `enum color { RED, GREEN = 5, BLUE }`.
+fn enum_definition<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, EnumDef, extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let enumerator = identifier()
+        .then(just(Token::Assign).ignore_then(expr_parser()).or_not())
+        .map_with(|(name, value), e| Enumerator {
+            name,
+            value,
+            span: e.span(),
+        });
+
+    just(Token::Enum)
+        .ignore_then(identifier().or_not())
+        .then(
+            enumerator
+                .separated_by(just(Token::Comma))
+                .at_least(1)
+                .allow_trailing()
+                .collect()
+                .delimited_by(just(Token::LeftBrace), just(Token::RightBrace)),
+        )
+        .map_with(|(tag, enumerators), e| EnumDef {
+            tag,
+            enumerators,
+            span: e.span(),
+        })
+}
+
+/// The type an enum definition specifies. One without a tag specifies `int`,
+/// which all enumerated types are compatible with.
+fn enum_type(definition: &EnumDef) -> Type {
+    definition.tag.clone().map_or(Type::Int, Type::Enum)
+}
+
+/// An enum definition such as `enum color { RED, GREEN = 5, BLUE };`, which
+/// may also declare a variable of the new type.
+pub fn enum_declaration<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (EnumDef, Option<Declaration>), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    enum_definition()
+        .then(choice((
+            just(Token::Semicolon).to(None),
+            init_declarator().map(Some),
+        )))
+        .map_with(|(definition, declarator), e| {
+            let ty = enum_type(&definition);
+            let declaration = declarator.map(|declarator| declarator.declare(ty, e.span()));
+            (definition, declaration)
+        })
+}
+
+/// A type defined in the specifier of a declaration.
+#[derive(Clone)]
+pub enum TypeDefinition {
+    Struct(StructDef),
+    Enum(EnumDef),
+}
+
+/// A typedef declaration such as `typedef unsigned long size_t;` or
+/// `typedef struct node { ... } node;`, along with the type it defines, if
+/// any.
+pub fn typedef_declaration<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (Option<TypeDefinition>, Typedef), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let specifier = choice((
+        struct_definition().map(|definition| {
+            let ty = definition.kind.named(definition.tag.clone());
+            (Some(TypeDefinition::Struct(definition)), ty)
+        }),
+        enum_definition().map(|definition| {
+            let ty = enum_type(&definition);
+            (Some(TypeDefinition::Enum(definition)), ty)
+        }),
+        type_specifier().map(|ty| (None, ty)),
+    ));
+
+    just(Token::Typedef)
+        .ignore_then(specifier)
+        .then(just(Token::Asterisk).repeated().count())
+        .then(identifier())
+        .then(array_size().repeated().collect::<Vec<_>>())
+        .then_ignore(just(Token::Semicolon))
+        .map_with(|((((definition, ty), pointers), name), dimensions), e| {
+            let ty = (0..pointers).fold(ty, |ty, _| Type::Pointer(Box::new(ty)));
+            let typedef = Typedef {
+                name,
+                ty: array_of(ty, dimensions),
+                span: e.span(),
+            };
+            (definition, typedef)
+        })
+}
+
+#[test]
+fn test_type_specifiers() {
+    use crate::{ast::Item, parser::parse};
+
+    let ty = |specifiers: &str| {
+        let ast = parse(&format!("{} x;", specifiers)).unwrap();
+        let Item::Global(decl) = &ast.program[0] else {
+            panic!("expected a global variable");
+        };
+        decl.var_type.clone()
+    };
+    assert_eq!(ty("int"), Type::Int);
+    assert_eq!(ty("signed"), Type::Int);
+    assert_eq!(ty("unsigned"), Type::UnsignedInt);
+    assert_eq!(ty("long"), Type::Long);
+    assert_eq!(ty("unsigned long int"), Type::UnsignedLong);
+    assert_eq!(ty("long unsigned"), Type::UnsignedLong);
+    assert_eq!(ty("long long"), Type::LongLong);
+    assert_eq!(ty("long int long unsigned"), Type::UnsignedLongLong);
+    assert_eq!(ty("float"), Type::Float);
+    assert_eq!(ty("char"), Type::Char);
+    assert_eq!(ty("signed char"), Type::Char);
+    assert_eq!(ty("char unsigned"), Type::UnsignedChar);
+
+    let error = |specifiers: &str| {
+        let errs = parse(&format!("{} x;", specifiers)).unwrap_err();
+        assert_eq!(errs.len(), 1, "{:#?}", errs);
+        errs[0].message.clone()
+    };
+    assert_eq!(error("int int"), "duplicate `int`");
+    assert_eq!(
+        error("unsigned float"),
+        "cannot combine `float` with previous `unsigned`"
+    );
+    assert_eq!(error("long long long"), "`long long long` is too long");
+    assert_eq!(error("short"), "type `short` is not supported");
+    assert_eq!(
+        error("long char"),
+        "cannot combine `char` with previous `long`"
+    );
+}
+
+#[test]
+fn test_reserved_identifiers() {
+    use crate::parser::parse;
+
+    let input = "int while(int if) {\n  int return = 3;\n  return 0;\n}\n";
+    let errs = parse(input).unwrap_err();
+    let messages: Vec<_> = errs.iter().map(|err| err.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "expected identifier, found keyword `while`",
+            "expected identifier, found keyword `if`",
+            "expected identifier, found keyword `return`",
+        ]
+    );
+    let span = errs[2].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "return");
+}
+
+#[test]
+fn test_array_declarators() {
+    use crate::{ast::Item, parser::parse};
+
+    let ast = parse("int a[10];\nfloat *m[4][2];").unwrap();
+    let types: Vec<_> = ast
+        .program
+        .iter()
+        .map(|item| match item {
+            Item::Global(decl) => decl.var_type.to_string(),
+            _ => panic!("expected a global variable"),
+        })
+        .collect();
+    assert_eq!(types, ["int [10]", "float *[4][2]"]);
+
+    let errs = parse("int a[0];\nint b[0x100000000];").unwrap_err();
+    let messages: Vec<_> = errs.iter().map(|err| err.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "array size must be greater than zero",
+            "array size `0x100000000` is too large",
+        ]
+    );
+}
+
+#[test]
+fn test_struct_declarations() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = "struct point { int x; float y[2]; };\n\
+                 struct node { int value; struct node *next; } *head;\n\
+                 struct point origin;";
+    let ast = parse(input).unwrap();
+    let [
+        Item::Struct(point),
+        Item::Struct(node),
+        Item::Global(head),
+        Item::Global(origin),
+    ] = ast.program.as_slice()
+    else {
+        panic!("expected two structs and two globals: {:#?}", ast.program);
+    };
+    let members: Vec<_> = point
+        .members
+        .iter()
+        .map(|m| format!("{} {}", m.member_type, m.name))
+        .collect();
+    assert_eq!(members, ["int x", "float [2] y"]);
+    assert_eq!(node.members[1].member_type.to_string(), "struct node *");
+    assert_eq!(head.var_type.to_string(), "struct node *");
+    assert_eq!(origin.var_type, Type::Struct("point".to_owned()));
+
+    let input = "struct empty {};";
+    let errs = parse(input).unwrap_err();
+    assert!(
+        errs[0].message.ends_with(", found `}`"),
+        "{}",
+        errs[0].message
+    );
+    let span = errs[0].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "}");
+
+    let ast = parse("union word { int i; float f; } w;").unwrap();
+    let [Item::Struct(word), Item::Global(w)] = ast.program.as_slice() else {
+        panic!("expected a union and a global: {:#?}", ast.program);
+    };
+    assert_eq!(word.kind, StructKind::Union);
+    assert_eq!(w.var_type.to_string(), "union word");
+}
+
+#[test]
+fn test_enum_declarations() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = "enum color { RED, GREEN = 5, BLUE, };\n\
+                 enum { IDLE, RUNNING = IDLE + 1 } state;\n\
+                 enum color c;";
+    let ast = parse(input).unwrap();
+    let [
+        Item::Enum(color),
+        Item::Enum(anonymous),
+        Item::Global(state),
+        Item::Global(c),
+    ] = ast.program.as_slice()
+    else {
+        panic!("expected two enums and two globals: {:#?}", ast.program);
+    };
+    assert_eq!(color.tag.as_deref(), Some("color"));
+    let names: Vec<_> = color.enumerators.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["RED", "GREEN", "BLUE"]);
+    assert!(color.enumerators[0].value.is_none());
+    assert!(color.enumerators[1].value.is_some());
+    assert_eq!(anonymous.tag, None);
+    assert_eq!(state.var_type, Type::Int);
+    assert_eq!(c.var_type, Type::Enum("color".to_owned()));
+
+    let input = "enum empty {};";
+    let errs = parse(input).unwrap_err();
+    assert!(
+        errs[0].message.ends_with(", found `}`"),
+        "{}",
+        errs[0].message
+    );
+    let span = errs[0].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "}");
+}
+
+#[test]
+fn test_typedef_declarations() {
+    use crate::{ast::Item, parser::parse};
+
+    let input = "typedef int score_t;\n\
+                 typedef struct node node;\n\
+                 typedef struct { score_t x; } point;\n\
+                 typedef node *list[4];\n\
+                 score_t best(point p, list l);";
+    let ast = parse(input).unwrap();
+    let [
+        Item::Typedef(score),
+        Item::Typedef(node),
+        Item::Struct(anonymous),
+        Item::Typedef(point),
+        Item::Typedef(list),
+        Item::Function(best),
+    ] = ast.program.as_slice()
+    else {
+        panic!("expected five typedefs and a function: {:#?}", ast.program);
+    };
+    assert_eq!((score.name.as_str(), &score.ty), ("score_t", &Type::Int));
+    assert_eq!(node.ty, Type::Struct("node".to_owned()));
+    assert_eq!(
+        anonymous.members[0].member_type,
+        Type::Typedef("score_t".to_owned())
+    );
+    assert_eq!(point.ty, Type::Struct(anonymous.tag.clone()));
+    let node_pointer = Type::Pointer(Box::new(Type::Typedef("node".to_owned())));
+    assert_eq!(list.ty, Type::Array(Box::new(node_pointer), 4));
+    assert_eq!(best.ret_type, Type::Typedef("score_t".to_owned()));
+    assert_eq!(best.params[1].param_type, Type::Typedef("list".to_owned()));
+
+    // a typedef name is only a type where it is declared as one
+    let input = "score_t x;";
+    let errs = parse(input).unwrap_err();
+    assert!(
+        errs[0].message.ends_with(", found identifier `score_t`"),
+        "{}",
+        errs[0].message
+    );
+    let span = errs[0].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "score_t");
+}
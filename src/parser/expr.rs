@@ -1,5 +1,11 @@
-use super::token::Token;
-use crate::ast::{BinaryOperator, Expression, LValue, Literal, UnaryOperator};
+use super::{
+    decl::{declared_type, identifier},
+    literal::int_literal,
+    token::Token,
+};
+use crate::ast::{
+    BinaryOperator, Expression, ExpressionKind, LValue, Literal, Type, UnaryOperator,
+};
 use chumsky::{input::ValueInput, prelude::*};
 
 pub fn expr_parser<'tokens, 'src: 'tokens, I>()
@@ -8,45 +14,180 @@ where
     I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
 {
     recursive(|expr| {
-        let literal = select! {
-            Token::IntLiteral(n) => Expression::Literal(Literal::Int(n.parse().unwrap())),
-            Token::Identifier(name) => Expression::Variable(name),
-        };
+        let integer = select! { Token::IntLiteral(n) => n }.validate(|n, e, emitter| {
+            let literal = int_literal(&n).unwrap_or_else(|msg| {
+                emitter.emit(Rich::custom(e.span(), msg));
+                Literal::Int {
+                    value: 0,
+                    ty: Type::Int,
+                }
+            });
+            ExpressionKind::Literal(literal)
+        });
+
+        // literals are `float`s, so one that only fits a `double` is too large
+        let float = select! { Token::FloatLiteral(n) => n }.validate(|n, e, emitter| {
+            let value = match n.parse::<f32>() {
+                Ok(value) if value.is_finite() => value,
+                _ => {
+                    emitter.emit(Rich::custom(
+                        e.span(),
+                        format!(
+                            "floating literal `{}` is too large to be represented in type `float`",
+                            n
+                        ),
+                    ));
+                    0.0
+                }
+            };
+            ExpressionKind::Literal(Literal::Float(value))
+        });
+
+        let variable = select! { Token::Identifier(name) => ExpressionKind::Variable(name) };
+
+        let literal = choice((integer, float, variable)).map_with(|kind, e| Expression {
+            kind,
+            span: e.span(),
+        });
 
+        // a parenthesized expression spans its parentheses, so that an
+        // operator applied to it spans all of its operand
         let primary = expr
             .clone()
             .delimited_by(just(Token::LeftParen), just(Token::RightParen))
-            .or(literal);
+            .map_with(|expr: Expression, e| Expression {
+                span: e.span(),
+                ..expr
+            })
+            .recover_with(via_parser(nested_delimiters(
+                Token::LeftParen,
+                Token::RightParen,
+                [(Token::LeftBrace, Token::RightBrace)],
+                |span| Expression {
+                    kind: ExpressionKind::Error,
+                    span,
+                },
+            )))
+            .or(literal)
+            .labelled("expression");
 
-        let function_call = primary
+        let arguments = expr
             .clone()
-            .then(
-                expr.clone()
-                    .separated_by(just(Token::Comma))
-                    .allow_trailing()
-                    .collect()
-                    .delimited_by(just(Token::LeftParen), just(Token::RightParen)),
-            )
-            .map(|(p, args)| Expression::FunctionCall {
-                callee: Box::new(p),
-                arguments: args,
-            })
-            .or(primary);
+            .separated_by(just(Token::Comma))
+            .allow_trailing()
+            .collect()
+            .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+            .recover_with(via_parser(nested_delimiters(
+                Token::LeftParen,
+                Token::RightParen,
+                [
+                    (Token::LeftBrace, Token::RightBrace),
+                    (Token::LeftBracket, Token::RightBracket),
+                ],
+                |span| {
+                    vec![Expression {
+                        kind: ExpressionKind::Error,
+                        span,
+                    }]
+                },
+            )));
+
+        let index = expr
+            .clone()
+            .delimited_by(just(Token::LeftBracket), just(Token::RightBracket))
+            .recover_with(via_parser(nested_delimiters(
+                Token::LeftBracket,
+                Token::RightBracket,
+                [
+                    (Token::LeftParen, Token::RightParen),
+                    (Token::LeftBrace, Token::RightBrace),
+                ],
+                |span| Expression {
+                    kind: ExpressionKind::Error,
+                    span,
+                },
+            )));
+
+        let member = choice((
+            just(Token::Dot)
+                .ignore_then(identifier())
+                .map(Postfix::Member),
+            just(Token::Arrow)
+                .ignore_then(identifier())
+                .map(Postfix::PointerMember),
+        ));
+
+        // postfix operators chain left to right, e.g. `m[i][j]`, `f(x)[0]`
+        // or `p->next->value`
+        let postfix = primary.foldl(
+            choice((
+                arguments.map(Postfix::Call),
+                index.map(|index| Postfix::Index(Box::new(index))),
+                member,
+            ))
+            .map_with(|op, e| (op, e.span()))
+            .repeated(),
+            |operand, (op, op_span): (_, SimpleSpan)| Expression {
+                span: (operand.span.start..op_span.end).into(),
+                kind: match op {
+                    Postfix::Call(arguments) => ExpressionKind::FunctionCall {
+                        callee: Box::new(operand),
+                        arguments,
+                    },
+                    Postfix::Index(index) => ExpressionKind::Subscript {
+                        array: Box::new(operand),
+                        index,
+                    },
+                    Postfix::Member(member) => ExpressionKind::Member {
+                        object: Box::new(operand),
+                        member,
+                    },
+                    Postfix::PointerMember(member) => ExpressionKind::Member {
+                        object: Box::new(Expression {
+                            span: operand.span,
+                            kind: ExpressionKind::Unary {
+                                operator: UnaryOperator::Deref,
+                                operand: Box::new(operand),
+                            },
+                        }),
+                        member,
+                    },
+                },
+            },
+        );
+
+        // a parenthesized type name, which can only start with a type
+        // specifier, unlike a parenthesized expression
+        let cast = declared_type()
+            .labelled("type name")
+            .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+            .map(Prefix::Cast);
 
+        // prefix operators and casts nest, e.g. `!!x`, `- -x`, `**p` or
+        // `(long)(unsigned)x`
         let unary = choice((
-            just(Token::Plus).to(UnaryOperator::Plus),
-            just(Token::Minus).to(UnaryOperator::Negate),
+            just(Token::Plus).to(Prefix::Operator(UnaryOperator::Plus)),
+            just(Token::Minus).to(Prefix::Operator(UnaryOperator::Negate)),
+            just(Token::Not).to(Prefix::Operator(UnaryOperator::Not)),
+            just(Token::Asterisk).to(Prefix::Operator(UnaryOperator::Deref)),
+            just(Token::Ampersand).to(Prefix::Operator(UnaryOperator::AddressOf)),
+            cast,
         ))
-        .or_not()
-        .then(function_call)
-        .map(|(op, fc)| {
-            if let Some(op) = op {
-                Expression::Unary {
-                    operator: op,
-                    operand: Box::new(fc),
-                }
-            } else {
-                fc
+        .map_with(|op, e| (op, e.span()))
+        .repeated()
+        .foldr(postfix, |(op, op_span): (_, SimpleSpan), operand| {
+            Expression {
+                span: (op_span.start..operand.span.end).into(),
+                kind: match op {
+                    Prefix::Operator(operator) => ExpressionKind::Unary {
+                        operator,
+                        operand: Box::new(operand),
+                    },
+                    Prefix::Cast(ty) => ExpressionKind::Cast {
+                        ty,
+                        operand: Box::new(operand),
+                    },
+                },
             }
         });
 
@@ -58,11 +199,7 @@ where
             ))
             .then(unary)
             .repeated(),
-            |l, (op, r)| Expression::Binary {
-                left: Box::new(l),
-                operator: op,
-                right: Box::new(r),
-            },
+            binary,
         );
 
         let additive = multiplicative.clone().foldl(
@@ -72,11 +209,7 @@ where
             ))
             .then(multiplicative)
             .repeated(),
-            |l, (op, r)| Expression::Binary {
-                left: Box::new(l),
-                operator: op,
-                right: Box::new(r),
-            },
+            binary,
         );
 
         let relational = additive.clone().foldl(
@@ -88,33 +221,97 @@ where
             ))
             .then(additive)
             .repeated(),
-            |l, (op, r)| Expression::Binary {
-                left: Box::new(l),
-                operator: op,
-                right: Box::new(r),
-            },
+            binary,
+        );
+
+        let equality = relational.clone().foldl(
+            choice((
+                just(Token::Equal).to(BinaryOperator::Equal),
+                just(Token::NotEqual).to(BinaryOperator::NotEqual),
+            ))
+            .then(relational)
+            .repeated(),
+            binary,
         );
 
-        // logical_or logical_and
+        let logical_and = equality.clone().foldl(
+            just(Token::And)
+                .to(BinaryOperator::And)
+                .then(equality)
+                .repeated(),
+            binary,
+        );
+
+        let logical_or = logical_and.clone().foldl(
+            just(Token::Or)
+                .to(BinaryOperator::Or)
+                .then(logical_and)
+                .repeated(),
+            binary,
+        );
 
         let assignment = recursive(|assignment| {
-            relational
+            logical_or
                 .clone()
                 .then(just(Token::Assign).ignore_then(assignment))
-                .map(|(var, value)| Expression::Assignment {
-                    target: match var {
-                        Expression::Variable(name) => LValue::Var(name),
-                        _ => panic!("Invalid assignment target"),
-                    },
-                    value: Box::new(value),
+                .validate(|(target, value): (Expression, Expression), e, emitter| {
+                    let span = e.span();
+                    match LValue::classify(target) {
+                        Ok(target) => Expression {
+                            kind: ExpressionKind::Assignment {
+                                target,
+                                value: Box::new(value),
+                            },
+                            span,
+                        },
+                        Err(target) => {
+                            emitter.emit(Rich::custom(target.span, "expression is not assignable"));
+                            Expression {
+                                kind: ExpressionKind::Error,
+                                span,
+                            }
+                        }
+                    }
                 })
-                .or(relational)
+                .or(logical_or)
         });
 
         assignment
     })
 }
 
+/// An operator preceding its operand.
+#[derive(Clone)]
+enum Prefix {
+    Operator(UnaryOperator),
+    /// `(type)`
+    Cast(Type),
+}
+
+/// An operator following its operand.
+enum Postfix {
+    /// `(arguments)` of a function call.
+    Call(Vec<Expression>),
+    /// `[index]` of a subscript.
+    Index(Box<Expression>),
+    /// `.member`
+    Member(String),
+    /// `->member`
+    PointerMember(String),
+}
+
+/// Folds `left op right` into a binary expression spanning both operands.
+fn binary(left: Expression, (operator, right): (BinaryOperator, Expression)) -> Expression {
+    Expression {
+        span: (left.span.start..right.span.end).into(),
+        kind: ExpressionKind::Binary {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        },
+    }
+}
+
 #[test]
 fn test() {
     use chumsky::input::Stream;
@@ -129,4 +326,260 @@ fn test() {
         Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
 
     let expr = expr_parser().parse(token_stream).into_result().unwrap();
+    assert_eq!((expr.span.start, expr.span.end), (0, input.len()));
+}
+
+#[test]
+fn test_logical_precedence() {
+    use chumsky::input::Stream;
+    use logos::Logos;
+    let input = r#"a || !!b && c == d != e"#;
+    let tokens = Token::lexer(&input).spanned().map(|(tok, span)| match tok {
+        Ok(t) => (t, span.into()),
+        Err(_) => (Token::Error, span.into()),
+    });
+
+    let token_stream =
+        Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+
+    let expr = expr_parser().parse(token_stream).into_result().unwrap();
+
+    // a || ((!(!b)) && ((c == d) != e))
+    assert_eq!((expr.span.start, expr.span.end), (0, input.len()));
+    let ExpressionKind::Binary {
+        operator: BinaryOperator::Or,
+        right,
+        ..
+    } = expr.kind
+    else {
+        panic!("expected `||` at the root");
+    };
+    assert_eq!((right.span.start, right.span.end), (5, input.len()));
+    let ExpressionKind::Binary {
+        left,
+        operator: BinaryOperator::And,
+        right,
+    } = right.kind
+    else {
+        panic!("expected `&&` below `||`");
+    };
+    let ExpressionKind::Unary {
+        operator: UnaryOperator::Not,
+        operand,
+    } = left.kind
+    else {
+        panic!("expected `!` as the left operand of `&&`");
+    };
+    assert_eq!((operand.span.start, operand.span.end), (6, 8));
+    assert!(matches!(
+        operand.kind,
+        ExpressionKind::Unary {
+            operator: UnaryOperator::Not,
+            ..
+        }
+    ));
+    let ExpressionKind::Binary {
+        left,
+        operator: BinaryOperator::NotEqual,
+        ..
+    } = right.kind
+    else {
+        panic!("expected `!=` as the right operand of `&&`");
+    };
+    assert!(matches!(
+        left.kind,
+        ExpressionKind::Binary {
+            operator: BinaryOperator::Equal,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_invalid_assignment_target() {
+    use crate::parser::parse;
+
+    for (input, target) in [
+        ("int main() { 1 = x; return 0; }", "1"),
+        ("int main() { f() = 3; return 0; }", "f()"),
+        ("int main() { int x; x + 1 = 2; return 0; }", "x + 1"),
+    ] {
+        let errs = parse(input).unwrap_err();
+        assert_eq!(errs.len(), 1, "{:#?}", errs);
+        assert_eq!(errs[0].message, "expression is not assignable");
+        let span = errs[0].span.unwrap();
+        assert_eq!(&input[span.start..span.end], target);
+    }
+
+    let errs = parse("int main() { return 99999999999999999999; }").unwrap_err();
+    assert_eq!(
+        errs[0].message,
+        "integer literal `99999999999999999999` is too large to be represented in any integer type"
+    );
+
+    let errs = parse("int main() { return 1e100; }").unwrap_err();
+    assert_eq!(errs.len(), 1, "{:#?}", errs);
+    assert_eq!(
+        errs[0].message,
+        "floating literal `1e100` is too large to be represented in type `float`"
+    );
+}
+
+#[test]
+fn test_pointer_operators() {
+    use crate::ast::LValueKind;
+    use chumsky::input::Stream;
+    use logos::Logos;
+    let input = r#"*p = a * *&b"#;
+    let tokens = Token::lexer(&input).spanned().map(|(tok, span)| match tok {
+        Ok(t) => (t, span.into()),
+        Err(_) => (Token::Error, span.into()),
+    });
+
+    let token_stream =
+        Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+
+    let expr = expr_parser().parse(token_stream).into_result().unwrap();
+
+    // (*p) = (a * (*(&b)))
+    let ExpressionKind::Assignment { target, value } = expr.kind else {
+        panic!("expected an assignment");
+    };
+    assert!(matches!(target.kind, LValueKind::Deref(_)));
+    assert_eq!((target.span.start, target.span.end), (0, 2));
+    let ExpressionKind::Binary {
+        operator: BinaryOperator::Multiply,
+        right,
+        ..
+    } = value.kind
+    else {
+        panic!("expected `*` as a binary operator");
+    };
+    let ExpressionKind::Unary {
+        operator: UnaryOperator::Deref,
+        operand,
+    } = right.kind
+    else {
+        panic!("expected `*` as a prefix operator");
+    };
+    assert!(matches!(
+        operand.kind,
+        ExpressionKind::Unary {
+            operator: UnaryOperator::AddressOf,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn test_casts() {
+    use crate::{
+        ast::{BlockItem, Item, StatementKind},
+        parser::parse,
+    };
+
+    let input = "typedef long wide; int main() { return (wide)(unsigned char *)x * (x); }";
+    let ast = parse(input).unwrap();
+    let Item::Function(main) = &ast.program[1] else {
+        panic!("expected a function");
+    };
+    let BlockItem::Stmt(stmt) = &main.body.as_ref().unwrap().items[0] else {
+        panic!("expected a statement");
+    };
+    let StatementKind::Ret(Some(expr)) = &stmt.kind else {
+        panic!("expected a return statement");
+    };
+
+    // ((wide)((unsigned char *)x)) * (x)
+    let ExpressionKind::Binary {
+        left,
+        operator: BinaryOperator::Multiply,
+        right,
+    } = &expr.kind
+    else {
+        panic!("expected `*` as a binary operator");
+    };
+    assert!(matches!(right.kind, ExpressionKind::Variable(_)));
+    let ExpressionKind::Cast { ty, operand } = &left.kind else {
+        panic!("expected a cast");
+    };
+    assert_eq!(*ty, Type::Typedef("wide".to_owned()));
+    assert_eq!(
+        &input[left.span.start..left.span.end],
+        "(wide)(unsigned char *)x"
+    );
+    let ExpressionKind::Cast { ty, .. } = &operand.kind else {
+        panic!("expected a nested cast");
+    };
+    assert_eq!(ty.to_string(), "unsigned char *");
+}
+
+#[test]
+fn test_subscripts() {
+    use crate::ast::LValueKind;
+    use chumsky::input::Stream;
+    use logos::Logos;
+    let input = r#"m[i][j + 1] = *a[0]"#;
+    let tokens = Token::lexer(&input).spanned().map(|(tok, span)| match tok {
+        Ok(t) => (t, span.into()),
+        Err(_) => (Token::Error, span.into()),
+    });
+
+    let token_stream =
+        Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+
+    let expr = expr_parser().parse(token_stream).into_result().unwrap();
+
+    // (m[i])[j + 1] = *(a[0])
+    let ExpressionKind::Assignment { target, value } = expr.kind else {
+        panic!("expected an assignment");
+    };
+    let LValueKind::Subscript { array, .. } = target.kind else {
+        panic!("expected a subscript as the assignment target");
+    };
+    assert_eq!((target.span.start, target.span.end), (0, 11));
+    assert_eq!((array.span.start, array.span.end), (0, 4));
+    assert!(matches!(array.kind, ExpressionKind::Subscript { .. }));
+    let ExpressionKind::Unary {
+        operator: UnaryOperator::Deref,
+        operand,
+    } = value.kind
+    else {
+        panic!("expected `*` to apply to the subscript");
+    };
+    assert!(matches!(operand.kind, ExpressionKind::Subscript { .. }));
+}
+
+#[test]
+fn test_member_access() {
+    use chumsky::input::Stream;
+    use logos::Logos;
+    let input = r#"a.b->c"#;
+    let tokens = Token::lexer(&input).spanned().map(|(tok, span)| match tok {
+        Ok(t) => (t, span.into()),
+        Err(_) => (Token::Error, span.into()),
+    });
+
+    let token_stream =
+        Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+
+    let expr = expr_parser().parse(token_stream).into_result().unwrap();
+
+    // (*(a.b)).c
+    let ExpressionKind::Member { object, member } = expr.kind else {
+        panic!("expected a member access");
+    };
+    assert_eq!(member, "c");
+    let ExpressionKind::Unary {
+        operator: UnaryOperator::Deref,
+        operand,
+    } = object.kind
+    else {
+        panic!("expected `->` to dereference its operand");
+    };
+    assert_eq!((operand.span.start, operand.span.end), (0, 3));
+    assert!(matches!(
+        operand.kind,
+        ExpressionKind::Member { ref member, .. } if member == "b"
+    ));
 }
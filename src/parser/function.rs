@@ -1,4 +1,8 @@
-use super::{block::block_parser, token::Token};
+use super::{
+    block::block_parser,
+    decl::{array_of, array_size, declared_type, identifier},
+    token::Token,
+};
 use crate::ast::{Function, Parameter, Type};
 use chumsky::{input::ValueInput, prelude::*};
 
@@ -7,31 +11,154 @@ pub fn function_parser<'tokens, 'src: 'tokens, I>()
 where
     I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
 {
-    let type_ = select! {
-        Token::Identifier(name) if name == "int" => Type::Int,
-        Token::Identifier(name) if name == "float" => Type::Float,
-        Token::Identifier(name) if name == "void" => Type::Void,
-    };
-
-    let parameter = type_
-        .clone()
-        .then(select! {Token::Identifier(name) => name})
-        .map(|(param_type, name)| Parameter { name, param_type });
+    // the outermost size of an array parameter may be left out, as it is
+    // adjusted to a pointer to the array's elements anyway: `int m[][4]`
+    // declares an `int (*)[4]`
+    let array_parameter = array_size()
+        .ignored()
+        .or(just(Token::LeftBracket)
+            .then(just(Token::RightBracket))
+            .ignored())
+        .or_not();
+    let parameter = declared_type()
+        .then(identifier().or_not())
+        .then(array_parameter)
+        .then(array_size().repeated().collect::<Vec<_>>())
+        .map_with(|(((ty, name), array), dimensions), e| Parameter {
+            name,
+            param_type: match array {
+                Some(()) => Type::Pointer(Box::new(array_of(ty, dimensions))),
+                None => ty,
+            },
+            span: e.span(),
+        });
 
+    // `...` may only follow at least one named parameter
+    let variadic = just(Token::Comma)
+        .then(just(Token::Ellipsis))
+        .or_not()
+        .map(|ellipsis| ellipsis.is_some());
     let param_list = parameter
         .separated_by(just(Token::Comma))
-        .allow_trailing()
+        .at_least(1)
         .collect()
-        .delimited_by(just(Token::LeftParen), just(Token::RightParen));
+        .then(variadic)
+        .or_not()
+        .map(Option::unwrap_or_default)
+        .delimited_by(just(Token::LeftParen), just(Token::RightParen))
+        // `(void)` declares a function without parameters
+        .map(
+            |(params, variadic): (Vec<Parameter>, bool)| match params.as_slice() {
+                [
+                    Parameter {
+                        name: None,
+                        param_type: Type::Void,
+                        ..
+                    },
+                ] if !variadic => (Vec::new(), false),
+                _ => (params, variadic),
+            },
+        )
+        .recover_with(via_parser(nested_delimiters(
+            Token::LeftParen,
+            Token::RightParen,
+            [(Token::LeftBrace, Token::RightBrace)],
+            |_| (Vec::new(), false),
+        )));
 
-    type_
-        .then(select! {Token::Identifier(name) => name})
+    // functions have external linkage whether or not `extern` is given
+    just(Token::Extern)
+        .or_not()
+        .ignore_then(declared_type())
+        .then(identifier())
         .then(param_list)
-        .then(block_parser())
-        .map(|(((ret_type, name), params), body)| Function {
-            name,
-            params,
-            body,
-            ret_type,
+        .then(choice((
+            block_parser().map(Some),
+            just(Token::Semicolon).to(None),
+        )))
+        .validate(
+            |(((ret_type, name), (params, variadic)), body), e, emitter| {
+                if body.is_some() {
+                    for param in params.iter().filter(|param| param.name.is_none()) {
+                        emitter.emit(Rich::custom(param.span, "parameter name omitted"));
+                    }
+                }
+                Function {
+                    name,
+                    params,
+                    variadic,
+                    body,
+                    ret_type,
+                    span: e.span(),
+                }
+            },
+        )
+}
+
+#[test]
+fn test_prototypes() {
+    use crate::{ast::Item, parser::parse};
+
+    let ast = parse("int f(int, float y);\nextern void g(void);\nint h() { return 0; }").unwrap();
+    let functions: Vec<_> = ast
+        .program
+        .iter()
+        .map(|item| match item {
+            Item::Function(function) => function,
+            _ => panic!("expected only functions"),
         })
+        .collect();
+    assert!(functions[0].body.is_none());
+    let names: Vec<_> = functions[0]
+        .params
+        .iter()
+        .map(|p| p.name.as_deref())
+        .collect();
+    assert_eq!(names, [None, Some("y")]);
+    assert!(functions[1].body.is_none());
+    assert!(functions[1].params.is_empty());
+    assert!(functions[2].body.is_some());
+
+    let errs = parse("int f(int) { return 0; }").unwrap_err();
+    assert_eq!(errs.len(), 1, "{:#?}", errs);
+    assert_eq!(errs[0].message, "parameter name omitted");
+}
+
+#[test]
+fn test_variadic_prototypes() {
+    use crate::{ast::Item, parser::parse};
+
+    let ast = parse("int printf(char *format, ...);\nint f(void);").unwrap();
+    let variadic: Vec<_> = ast
+        .program
+        .iter()
+        .map(|item| match item {
+            Item::Function(function) => function.variadic,
+            _ => panic!("expected only functions"),
+        })
+        .collect();
+    assert_eq!(variadic, [true, false]);
+
+    // `...` comes last, after at least one parameter and a comma
+    for (input, found) in [
+        ("int f(...);", "found `...`"),
+        ("int f(int x ...);", "found `...`"),
+        ("int f(int x, ..., int y);", "found `,`"),
+    ] {
+        let errs = parse(input).unwrap_err();
+        assert_eq!(errs.len(), 1, "{:#?}", errs);
+        assert!(errs[0].message.ends_with(found), "{}", errs[0].message);
+    }
+}
+
+#[test]
+fn test_array_parameters() {
+    use crate::{ast::Item, parser::parse};
+
+    let ast = parse("int f(int a[], float m[3][4], int *v[8]);").unwrap();
+    let Item::Function(f) = &ast.program[0] else {
+        panic!("expected a function");
+    };
+    let types: Vec<_> = f.params.iter().map(|p| p.param_type.to_string()).collect();
+    assert_eq!(types, ["int *", "float (*)[4]", "int **"]);
 }
@@ -0,0 +1,127 @@
+use crate::ast::{Literal, Type};
+
+/// Parses an integer constant such as `42`, `0x1F`, `017`, `0b101` or
+/// `10ul`, giving it the first type in C's candidate list (C11 6.4.4.1)
+/// that can represent its value.
+pub fn int_literal(text: &str) -> Result<Literal, String> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let suffix = text[digits.len()..].to_ascii_lowercase();
+
+    let (radix, digits) = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        (16, hex)
+    } else if let Some(binary) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        (2, binary)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+        let base = match radix {
+            2 => "binary",
+            8 => "octal",
+            10 => "decimal",
+            _ => "hexadecimal",
+        };
+        return Err(format!("invalid digit `{}` in {} constant", digit, base));
+    }
+    let value = u64::from_str_radix(digits, radix).map_err(|_| {
+        format!(
+            "integer literal `{}` is too large to be represented in any integer type",
+            text
+        )
+    })?;
+
+    let unsigned = suffix.contains('u');
+    let long = suffix.matches('l').count();
+    let candidates: &[Type] = match (unsigned, long, radix == 10) {
+        (false, 0, true) => &[Type::Int, Type::Long, Type::LongLong],
+        (false, 0, false) => &[
+            Type::Int,
+            Type::UnsignedInt,
+            Type::Long,
+            Type::UnsignedLong,
+            Type::LongLong,
+            Type::UnsignedLongLong,
+        ],
+        (true, 0, _) => &[
+            Type::UnsignedInt,
+            Type::UnsignedLong,
+            Type::UnsignedLongLong,
+        ],
+        (false, 1, true) => &[Type::Long, Type::LongLong],
+        (false, 1, false) => &[
+            Type::Long,
+            Type::UnsignedLong,
+            Type::LongLong,
+            Type::UnsignedLongLong,
+        ],
+        (true, 1, _) => &[Type::UnsignedLong, Type::UnsignedLongLong],
+        (false, _, true) => &[Type::LongLong],
+        (false, _, false) => &[Type::LongLong, Type::UnsignedLongLong],
+        (true, _, _) => &[Type::UnsignedLongLong],
+    };
+
+    match candidates.iter().find(|ty| value <= ty.max_value()) {
+        Some(ty) => Ok(Literal::Int {
+            value,
+            ty: ty.clone(),
+        }),
+        None => Err(format!(
+            "integer literal `{}` is too large to be represented in a signed integer type",
+            text
+        )),
+    }
+}
+
+#[test]
+fn test_int_literal() {
+    let typed = |text| match int_literal(text).unwrap() {
+        Literal::Int { value, ty } => (value, ty),
+        Literal::Float(_) => unreachable!(),
+    };
+
+    assert_eq!(typed("0"), (0, Type::Int));
+    assert_eq!(typed("2147483647"), (2147483647, Type::Int));
+    assert_eq!(typed("2147483648"), (2147483648, Type::Long));
+    assert_eq!(typed("0x7fffffff"), (0x7fffffff, Type::Int));
+    assert_eq!(typed("0xFFFFFFFF"), (0xffffffff, Type::UnsignedInt));
+    assert_eq!(typed("0x100000000"), (0x100000000, Type::Long));
+    assert_eq!(typed("0xffffffffffffffff"), (u64::MAX, Type::UnsignedLong));
+    assert_eq!(typed("017"), (15, Type::Int));
+    assert_eq!(typed("0b101"), (5, Type::Int));
+    assert_eq!(typed("10u"), (10, Type::UnsignedInt));
+    assert_eq!(typed("10l"), (10, Type::Long));
+    assert_eq!(typed("10UL"), (10, Type::UnsignedLong));
+    assert_eq!(typed("10lu"), (10, Type::UnsignedLong));
+    assert_eq!(typed("10ll"), (10, Type::LongLong));
+    assert_eq!(typed("10LLU"), (10, Type::UnsignedLongLong));
+
+    assert_eq!(
+        int_literal("09").unwrap_err(),
+        "invalid digit `9` in octal constant"
+    );
+    assert_eq!(
+        int_literal("0b102").unwrap_err(),
+        "invalid digit `2` in binary constant"
+    );
+    assert_eq!(
+        int_literal("18446744073709551616").unwrap_err(),
+        "integer literal `18446744073709551616` is too large to be represented in any integer type"
+    );
+    assert_eq!(
+        int_literal("9223372036854775808").unwrap_err(),
+        "integer literal `9223372036854775808` is too large to be represented in a signed integer type"
+    );
+    assert_eq!(
+        typed("9223372036854775808u"),
+        (9223372036854775808, Type::UnsignedLong)
+    );
+}
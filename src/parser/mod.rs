@@ -1,54 +1,431 @@
 mod block;
+mod decl;
 mod expr;
 mod function;
+mod literal;
 mod program;
+mod recovery;
 mod token;
+mod type_names;
 
-use crate::ast::Ast;
+use crate::{ast::Ast, diagnostics::Diagnostic};
 use chumsky::{
     Parser,
-    error::Rich,
+    error::{Rich, RichPattern, RichReason},
     input::{Input, Stream},
+    span::SimpleSpan,
 };
-use eros::{Context, IntoDynTracedError, Result};
 use logos::Logos;
 use program::program_parser;
-use token::Token;
+use token::LexError;
+pub use token::Token;
 
-pub fn parse(input: &str) -> Result<Ast> {
-    let tokens = Token::lexer(&input).spanned().map(|(tok, span)| match tok {
-        Ok(t) => (t, span.into()),
-        Err(_) => (Token::Error, span.into()),
-    });
+/// Splits `input` into tokens with their byte spans. Malformed input is
+/// reported and kept out of the token stream, so that the parser does not
+/// report it a second time.
+pub fn lex(input: &str) -> (Vec<(Token, SimpleSpan)>, Vec<Diagnostic>) {
+    let (text, splices) = splice_lines(input);
+    let mut tokens = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut lexer = Token::lexer(&text)
+        .spanned()
+        .map(|(tok, span)| (tok, SimpleSpan::from(span)))
+        .peekable();
 
-    let token_stream =
-        Stream::from_iter(tokens).map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+    while let Some((tok, span)) = lexer.next() {
+        let tok = match tok {
+            Ok(tok) => tok,
+            Err(err) => {
+                // the rest of a multi-byte character is covered by the error
+                // at its first byte
+                if text.is_char_boundary(span.start) {
+                    let span = match err {
+                        // point at the opening `/*` rather than at the rest
+                        // of the file
+                        LexError::UnterminatedComment => (span.start..span.start + 2).into(),
+                        _ => char_span(&text, span),
+                    };
+                    let message = err.message(&text[span.start..span.end]);
+                    diagnostics.push(Diagnostic::error(message).at(unsplice(&splices, span)));
+                }
+                continue;
+            }
+        };
+        if !tok.is_number() {
+            tokens.push((tok, unsplice(&splices, span)));
+            continue;
+        }
+
+        // like C, read `12ab` or `1.2.3` as a single malformed number rather
+        // than as adjacent tokens
+        let mut end = span.end;
+        while let Some((Ok(next), next_span)) = lexer.peek() {
+            let word = matches!(next, Token::Identifier(_)) || next.keyword().is_some();
+            if next_span.start != end || !(next.is_number() || word) {
+                break;
+            }
+            end = next_span.end;
+            lexer.next();
+        }
+        if end == span.end {
+            tokens.push((tok, unsplice(&splices, span)));
+        } else {
+            let span = SimpleSpan::from(span.start..end);
+            diagnostics.push(
+                Diagnostic::error(format!("malformed number `{}`", &text[span.start..end]))
+                    .at(unsplice(&splices, span)),
+            );
+            // stands in for the number so that parsing carries on
+            tokens.push((Token::IntLiteral("0".to_owned()), unsplice(&splices, span)));
+        }
+    }
+    (tokens, diagnostics)
+}
 
-    program_parser()
-        .parse(token_stream)
-        .into_result()
-        .map_err(|e| ParseError::from_vec_rich(e))
-        .traced_dyn()
-        .context("ParseError")
+/// Deletes every backslash-newline, joining the lines around it, as C does
+/// before splitting a file into tokens. Returns the joined text and, for each
+/// deletion, its offset in that text and the number of bytes deleted up to
+/// and including it.
+fn splice_lines(input: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::with_capacity(input.len());
+    let mut splices = Vec::new();
+    let mut rest = input;
+    while let Some(i) = rest.find('\\') {
+        text.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let newline = if after.starts_with("\r\n") {
+            2
+        } else if after.starts_with('\n') {
+            1
+        } else {
+            0
+        };
+        if newline == 0 {
+            text.push('\\');
+        } else {
+            let removed = splices.last().map_or(0, |&(_, removed)| removed) + 1 + newline;
+            splices.push((text.len(), removed));
+        }
+        rest = &after[newline..];
+    }
+    text.push_str(rest);
+    (text, splices)
 }
 
-#[derive(Debug, Clone)]
-struct ParseError(String);
+/// Maps a span of the joined text built by [`splice_lines`] back to the
+/// original input.
+fn unsplice(splices: &[(usize, usize)], span: SimpleSpan) -> SimpleSpan {
+    // a deletion at the very start of a span lies before it, one at the very
+    // end lies after it
+    let removed_before = |offset: usize, inclusive: bool| {
+        splices
+            .iter()
+            .take_while(|&&(at, _)| at < offset || (inclusive && at == offset))
+            .last()
+            .map_or(0, |&(_, removed)| removed)
+    };
+    let start = span.start + removed_before(span.start, true);
+    let end = span.end + removed_before(span.end, false);
+    (start..end.max(start)).into()
+}
+
+/// Widens `span` to whole characters.
+fn char_span(input: &str, span: SimpleSpan) -> SimpleSpan {
+    let mut end = span.end.max(span.start + 1).min(input.len());
+    while !input.is_char_boundary(end) {
+        end += 1;
+    }
+    (span.start..end).into()
+}
+
+/// Parses `input`, returning every syntax error as a diagnostic on failure.
+pub fn parse(input: &str) -> Result<Ast, Vec<Diagnostic>> {
+    match parse_partial(input) {
+        (Some(ast), errors) if errors.is_empty() => Ok(ast),
+        (_, errors) => Err(errors),
+    }
+}
 
-impl ParseError {
-    fn from_vec_rich(errs: Vec<Rich<'_, Token>>) -> Self {
-        let mut msg = String::new();
-        for err in errs {
-            msg.push_str(&format!("{}\n", err));
+/// Parses `input`, recovering from syntax errors. Returns whatever could be
+/// parsed, with malformed statements and expressions replaced by error
+/// nodes, along with every syntax error found.
+pub fn parse_partial(input: &str) -> (Option<Ast>, Vec<Diagnostic>) {
+    let (mut tokens, mut diagnostics) = lex(input);
+    type_names::mark(&mut tokens);
+
+    let token_stream = Stream::from_iter(tokens.clone())
+        .map((0..input.len()).into(), |(tok, span): (_, _)| (tok, span));
+
+    let (ast, errs) = program_parser().parse(token_stream).into_output_errors();
+    diagnostics.extend(errs.iter().map(|err| diagnostic_from_rich(err, &tokens)));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+    (ast, diagnostics)
+}
+
+fn diagnostic_from_rich(err: &Rich<'_, Token>, tokens: &[(Token, SimpleSpan)]) -> Diagnostic {
+    let span = *err.span();
+    let found = err
+        .found()
+        .map_or_else(|| "end of input".to_owned(), Token::describe);
+    let mut expected: Vec<String> = err.expected().map(describe_pattern).collect();
+    expected.sort();
+    expected.dedup();
+
+    let mut diagnostic = match err.reason() {
+        RichReason::Custom(msg) => Diagnostic::error(msg.to_string()).at(span),
+        _ if expected.is_empty() => Diagnostic::error(format!("unexpected {}", found))
+            .at(span)
+            .with_label("unexpected token"),
+        _ => {
+            let expected = alternatives(&expected);
+            Diagnostic::error(format!("expected {}, found {}", expected, found))
+                .at(span)
+                .with_label(format!("expected {}", expected))
+        }
+    };
+
+    if let Some((open, open_span)) = unclosed_delimiter(tokens, span.start) {
+        let close = closing_delimiter(open).expect("only opening delimiters are tracked");
+        // a closer for an outer delimiter, or running out of input, means the
+        // innermost one was never closed
+        let runs_into_close = match err.found() {
+            None => true,
+            Some(found) => {
+                closing_delimiter_of(found).is_some_and(|opener| opener != *open)
+                    || err
+                        .expected()
+                        .any(|pattern| matches!(pattern, RichPattern::Token(t) if **t == close))
+            }
+        };
+        if runs_into_close {
+            diagnostic =
+                diagnostic.with_secondary(open_span, format!("unclosed `{}` opened here", open));
         }
-        ParseError(msg)
     }
+    diagnostic
 }
 
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ParseError: {}", self.0)
+fn describe_pattern(pattern: &RichPattern<'_, Token>) -> String {
+    match pattern {
+        RichPattern::Token(token) => token.describe(),
+        RichPattern::Label(label) => label.to_string(),
+        RichPattern::EndOfInput => "end of input".to_owned(),
+        _ => "something else".to_owned(),
+    }
+}
+
+/// Joins alternatives as "`a`", "`a` or `b`" or "one of `a`, `b`, `c`".
+fn alternatives(items: &[String]) -> String {
+    match items {
+        [only] => only.clone(),
+        [first, second] => format!("{} or {}", first, second),
+        _ => format!("one of {}", items.join(", ")),
+    }
+}
+
+/// The innermost `(` or `{` still open at byte offset `before`.
+fn unclosed_delimiter(
+    tokens: &[(Token, SimpleSpan)],
+    before: usize,
+) -> Option<(&Token, SimpleSpan)> {
+    let mut open = Vec::new();
+    for (token, span) in tokens.iter().take_while(|(_, span)| span.start < before) {
+        if closing_delimiter(token).is_some() {
+            open.push((token, *span));
+        } else if let Some(opener) = closing_delimiter_of(token) {
+            if open.last().is_some_and(|(top, _)| *top == &opener) {
+                open.pop();
+            }
+        }
     }
+    open.pop()
 }
 
-impl std::error::Error for ParseError {}
+fn closing_delimiter(token: &Token) -> Option<Token> {
+    match token {
+        Token::LeftParen => Some(Token::RightParen),
+        Token::LeftBrace => Some(Token::RightBrace),
+        Token::LeftBracket => Some(Token::RightBracket),
+        _ => None,
+    }
+}
+
+/// The opening delimiter matched by `token`, if it is a closing one.
+fn closing_delimiter_of(token: &Token) -> Option<Token> {
+    match token {
+        Token::RightParen => Some(Token::LeftParen),
+        Token::RightBrace => Some(Token::LeftBrace),
+        Token::RightBracket => Some(Token::LeftBracket),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_diagnostics() {
+    let errs = parse("int main() {\n  return 0\n}\n").unwrap_err();
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0].message.contains("`;`"), "{}", errs[0].message);
+    assert!(
+        errs[0].message.ends_with(", found `}`"),
+        "{}",
+        errs[0].message
+    );
+    assert_eq!(errs[0].span.map(|span| span.start), Some(24));
+    assert!(errs[0].secondary.is_empty());
+
+    let errs = parse("int main() {\n  return 0;\n").unwrap_err();
+    assert!(errs[0].message.contains("`}`"), "{}", errs[0].message);
+    assert!(errs[0].message.ends_with("found end of input"));
+    let unclosed = &errs[0].secondary[0];
+    assert_eq!(unclosed.message, "unclosed `{` opened here");
+    assert_eq!((unclosed.span.start, unclosed.span.end), (11, 12));
+
+    let errs = parse("int main() { return f(1; }").unwrap_err();
+    assert_eq!(errs[0].secondary[0].message, "unclosed `(` opened here");
+}
+
+#[test]
+fn test_recovery() {
+    use crate::ast::{BlockItem, ExpressionKind, Item, StatementKind};
+
+    let input = r#"
+        int f(int x) {
+          int y = x +;
+          return y
+        }
+
+        int g(int) { return 1; }
+
+        int main() {
+          if (1 +) f(1);
+          while (0) { 2 3; }
+          return f(2);
+        }
+    "#;
+    let (ast, errs) = parse_partial(input);
+    let ast = ast.unwrap();
+    assert_eq!(errs.len(), 5, "{:#?}", errs);
+
+    let functions: Vec<_> = ast
+        .program
+        .iter()
+        .map(|item| match item {
+            Item::Function(function) => function,
+            _ => panic!("expected only functions"),
+        })
+        .collect();
+    let names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["f", "g", "main"]);
+
+    // both statements of `f` are replaced by error nodes
+    assert!(
+        functions[0]
+            .body
+            .as_ref()
+            .unwrap()
+            .items
+            .iter()
+            .all(|item| matches!(
+                item,
+                BlockItem::Stmt(stmt) if matches!(stmt.kind, StatementKind::Error)
+            ))
+    );
+    assert!(functions[1].params[0].name.is_none());
+
+    let main = functions[2].body.as_ref().unwrap();
+    let BlockItem::Stmt(stmt) = &main.items[0] else {
+        panic!("expected a statement");
+    };
+    let StatementKind::If { condition, .. } = &stmt.kind else {
+        panic!("expected the if statement to survive");
+    };
+    assert!(matches!(condition.kind, ExpressionKind::Error));
+    assert_eq!(main.items.len(), 3);
+
+    let errs = parse(input).unwrap_err();
+    let found: Vec<_> = errs
+        .iter()
+        .map(|err| {
+            let span = err.span.unwrap();
+            &input[span.start..span.end]
+        })
+        .collect();
+    assert_eq!(found, [";", "}", "int", ")", "3"]);
+    assert_eq!(errs[2].message, "parameter name omitted");
+    for (err, found) in [
+        (&errs[0], "`;`"),
+        (&errs[1], "`}`"),
+        (&errs[3], "`)`"),
+        (&errs[4], "integer literal `3`"),
+    ] {
+        let suffix = format!(", found {}", found);
+        assert!(err.message.ends_with(&suffix), "{}", err.message);
+    }
+}
+
+#[test]
+fn test_lex_errors() {
+    let input = "int @x = 12ab + \"oops;\nreturn \u{e9};";
+    let (tokens, errs) = lex(input);
+    let messages: Vec<_> = errs.iter().map(|err| err.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "unexpected character `@`",
+            "malformed number `12ab`",
+            "missing terminating `\"` character",
+            "non-ASCII character `\u{e9}` (U+00E9) in source",
+        ]
+    );
+    let span = errs[3].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "\u{e9}");
+
+    let tokens: Vec<_> = tokens.into_iter().map(|(tok, _)| tok).collect();
+    assert_eq!(
+        tokens,
+        [
+            Token::Int,
+            Token::Identifier("x".to_owned()),
+            Token::Assign,
+            Token::IntLiteral("0".to_owned()),
+            Token::Plus,
+            Token::Return,
+            Token::Semicolon,
+        ]
+    );
+
+    // a stray character is reported once, without parser errors after it
+    let errs = parse("int main() { return 1 $; }").unwrap_err();
+    assert_eq!(errs.len(), 1, "{:#?}", errs);
+}
+
+#[test]
+fn test_line_splicing() {
+    let input = "int ma\\\r\nin() {\\\n  return 1; // note \\\n  return 2;\r\n}\r\n";
+    let (tokens, errs) = lex(input);
+    assert!(errs.is_empty(), "{:#?}", errs);
+    let spans: Vec<_> = tokens
+        .iter()
+        .map(|(tok, span)| (tok.clone(), &input[span.start..span.end]))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            (Token::Int, "int"),
+            (Token::Identifier("main".to_owned()), "ma\\\r\nin"),
+            (Token::LeftParen, "("),
+            (Token::RightParen, ")"),
+            (Token::LeftBrace, "{"),
+            (Token::Return, "return"),
+            (Token::IntLiteral("1".to_owned()), "1"),
+            (Token::Semicolon, ";"),
+            (Token::RightBrace, "}"),
+        ]
+    );
+
+    let input = "int main() {\n  return 0; /* oops\n}\n";
+    let errs = parse(input).unwrap_err();
+    assert_eq!(errs[0].message, "unterminated `/*` comment");
+    let span = errs[0].span.unwrap();
+    assert_eq!(&input[span.start..span.end], "/*");
+}
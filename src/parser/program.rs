@@ -1,5 +1,13 @@
-use super::{function::function_parser, token::Token};
-use crate::ast::Ast;
+use super::{
+    decl::{
+        TypeDefinition, declaration_parser, enum_declaration, struct_declaration,
+        typedef_declaration,
+    },
+    function::function_parser,
+    recovery::skip_item,
+    token::Token,
+};
+use crate::ast::{Ast, Item};
 use chumsky::{input::ValueInput, prelude::*};
 
 pub fn program_parser<'tokens, 'src: 'tokens, I>()
@@ -7,8 +15,37 @@ pub fn program_parser<'tokens, 'src: 'tokens, I>()
 where
     I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
 {
-    function_parser()
-        .repeated()
-        .collect()
-        .map(|functions| Ast { program: functions })
+    choice((
+        function_parser().map(|function| vec![Item::Function(function)]),
+        declaration_parser().map(|decl| vec![Item::Global(decl)]),
+        struct_declaration().map(|(definition, decl)| {
+            std::iter::once(Item::Struct(definition))
+                .chain(decl.map(Item::Global))
+                .collect()
+        }),
+        enum_declaration().map(|(definition, decl)| {
+            std::iter::once(Item::Enum(definition))
+                .chain(decl.map(Item::Global))
+                .collect()
+        }),
+        typedef_declaration().map(|(definition, typedef)| {
+            definition
+                .map(|definition| match definition {
+                    TypeDefinition::Struct(definition) => Item::Struct(definition),
+                    TypeDefinition::Enum(definition) => Item::Enum(definition),
+                })
+                .into_iter()
+                .chain(std::iter::once(Item::Typedef(typedef)))
+                .collect()
+        }),
+    ))
+    .recover_with(via_parser(skip_item().to(Vec::new())))
+    .repeated()
+    .collect::<Vec<_>>()
+    // input no item can be recovered from, such as a function body that
+    // is never closed, is reported and dropped
+    .then_ignore(end().recover_with(via_parser(any().repeated())))
+    .map(|items| Ast {
+        program: items.into_iter().flatten().collect(),
+    })
 }
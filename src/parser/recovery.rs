@@ -0,0 +1,54 @@
+use super::token::Token;
+use chumsky::{input::ValueInput, prelude::*};
+
+/// A `{ ... }` group, including any groups nested inside it.
+pub fn braces<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    recursive(|braces| {
+        choice((
+            braces,
+            none_of([Token::LeftBrace, Token::RightBrace]).ignored(),
+        ))
+        .repeated()
+        .delimited_by(just(Token::LeftBrace), just(Token::RightBrace))
+    })
+}
+
+/// Skips a malformed block item: up to and including the next `;` or brace
+/// group at the same nesting level, or up to the `}` closing the enclosing
+/// block.
+pub fn skip_statement<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    let other = none_of([Token::Semicolon, Token::LeftBrace, Token::RightBrace]);
+    choice((
+        other
+            .clone()
+            .repeated()
+            .then(choice((just(Token::Semicolon).ignored(), braces())))
+            .ignored(),
+        other.repeated().at_least(1),
+    ))
+}
+
+/// Skips a malformed top-level item: up to and including the next `;` or
+/// brace group, or a stray `}`.
+pub fn skip_item<'tokens, 'src: 'tokens, I>()
+-> impl Parser<'tokens, I, (), extra::Err<Rich<'tokens, Token>>> + Clone
+where
+    I: ValueInput<'tokens, Token = Token, Span = SimpleSpan>,
+{
+    none_of([Token::Semicolon, Token::LeftBrace, Token::RightBrace])
+        .repeated()
+        .then(choice((
+            just(Token::Semicolon).ignored(),
+            braces(),
+            just(Token::RightBrace).ignored(),
+        )))
+        .ignored()
+}
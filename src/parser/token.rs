@@ -1,8 +1,12 @@
 use logos::Logos;
 
 #[derive(Logos, Debug, PartialEq, Clone, Eq)]
-#[logos(skip r"[ \t\n\f]+")]
+#[logos(skip r"[ \t\r\n\f]+")]
+#[logos(skip r"//[^\n]*")]
+#[logos(skip r"/\*([^*]|\*+[^*/])*\*+/")]
+#[logos(error = LexError)]
 pub enum Token {
+    #[regex(r"/\*([^*]|\*+[^*/])*\**", unterminated_comment)]
     Error,
     #[token("(")]
     LeftParen,
@@ -12,10 +16,16 @@ pub enum Token {
     LeftBrace,
     #[token("}")]
     RightBrace,
+    #[token("[")]
+    LeftBracket,
+    #[token("]")]
+    RightBracket,
     #[token(";")]
     Semicolon,
     #[token(",")]
     Comma,
+    #[token(":")]
+    Colon,
     #[token("+")]
     Plus,
     #[token("-")]
@@ -46,18 +56,223 @@ pub enum Token {
     Assign,
     #[token("!")]
     Not,
-    #[regex(r"[0-9]+", |l| l.slice().to_owned())]
+    #[token("&")]
+    Ampersand,
+    #[token(".")]
+    Dot,
+    #[token("->")]
+    Arrow,
+    #[token("...")]
+    Ellipsis,
+    #[regex(r"(0[xX][0-9a-fA-F]+|0[bB][01]+|[0-9]+)([uU](l|L|ll|LL)?|(l|L|ll|LL)[uU]?)?", |l| l.slice().to_owned())]
     IntLiteral(String),
+    #[regex(r"([0-9]+\.[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?|[0-9]+[eE][+-]?[0-9]+", |l| l.slice().to_owned())]
+    FloatLiteral(String),
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |l| l.slice().to_owned())]
     Identifier(String),
-    #[token("if")]
-    If,
+    /// An identifier that names a typedef in the scope it appears in. The
+    /// lexer only produces `Identifier`s; see `type_names::mark`.
+    TypeName(String),
+    #[regex(r#""([^"\\\n]|\\.)*""#, |l| l.slice().to_owned())]
+    #[regex(r#""([^"\\\n]|\\.)*"#, unterminated_string)]
+    StringLiteral(String),
+    // keywords (C11 6.4.1)
+    #[token("auto")]
+    Auto,
+    #[token("break")]
+    Break,
+    #[token("case")]
+    Case,
+    #[token("char")]
+    Char,
+    #[token("const")]
+    Const,
+    #[token("continue")]
+    Continue,
+    #[token("default")]
+    Default,
+    #[token("do")]
+    Do,
+    #[token("double")]
+    Double,
     #[token("else")]
     Else,
-    #[token("while")]
-    While,
+    #[token("enum")]
+    Enum,
+    #[token("extern")]
+    Extern,
+    #[token("float")]
+    Float,
+    #[token("for")]
+    For,
+    #[token("goto")]
+    Goto,
+    #[token("if")]
+    If,
+    #[token("inline")]
+    Inline,
+    #[token("int")]
+    Int,
+    #[token("long")]
+    Long,
+    #[token("register")]
+    Register,
+    #[token("restrict")]
+    Restrict,
     #[token("return")]
     Return,
+    #[token("short")]
+    Short,
+    #[token("signed")]
+    Signed,
+    #[token("sizeof")]
+    Sizeof,
+    #[token("static")]
+    Static,
+    #[token("struct")]
+    Struct,
+    #[token("switch")]
+    Switch,
+    #[token("typedef")]
+    Typedef,
+    #[token("union")]
+    Union,
+    #[token("unsigned")]
+    Unsigned,
+    #[token("void")]
+    Void,
+    #[token("volatile")]
+    Volatile,
+    #[token("while")]
+    While,
+    #[token("_Alignas")]
+    Alignas,
+    #[token("_Alignof")]
+    Alignof,
+    #[token("_Atomic")]
+    Atomic,
+    #[token("_Bool")]
+    Bool,
+    #[token("_Complex")]
+    Complex,
+    #[token("_Generic")]
+    Generic,
+    #[token("_Imaginary")]
+    Imaginary,
+    #[token("_Noreturn")]
+    Noreturn,
+    #[token("_Static_assert")]
+    StaticAssert,
+    #[token("_Thread_local")]
+    ThreadLocal,
+}
+
+impl Token {
+    pub fn is_number(&self) -> bool {
+        matches!(self, Token::IntLiteral(_) | Token::FloatLiteral(_))
+    }
+
+    /// The spelling of a keyword token, `None` for any other token.
+    pub fn keyword(&self) -> Option<&'static str> {
+        Some(match self {
+            Token::Auto => "auto",
+            Token::Break => "break",
+            Token::Case => "case",
+            Token::Char => "char",
+            Token::Const => "const",
+            Token::Continue => "continue",
+            Token::Default => "default",
+            Token::Do => "do",
+            Token::Double => "double",
+            Token::Else => "else",
+            Token::Enum => "enum",
+            Token::Extern => "extern",
+            Token::Float => "float",
+            Token::For => "for",
+            Token::Goto => "goto",
+            Token::If => "if",
+            Token::Inline => "inline",
+            Token::Int => "int",
+            Token::Long => "long",
+            Token::Register => "register",
+            Token::Restrict => "restrict",
+            Token::Return => "return",
+            Token::Short => "short",
+            Token::Signed => "signed",
+            Token::Sizeof => "sizeof",
+            Token::Static => "static",
+            Token::Struct => "struct",
+            Token::Switch => "switch",
+            Token::Typedef => "typedef",
+            Token::Union => "union",
+            Token::Unsigned => "unsigned",
+            Token::Void => "void",
+            Token::Volatile => "volatile",
+            Token::While => "while",
+            Token::Alignas => "_Alignas",
+            Token::Alignof => "_Alignof",
+            Token::Atomic => "_Atomic",
+            Token::Bool => "_Bool",
+            Token::Complex => "_Complex",
+            Token::Generic => "_Generic",
+            Token::Imaginary => "_Imaginary",
+            Token::Noreturn => "_Noreturn",
+            Token::StaticAssert => "_Static_assert",
+            Token::ThreadLocal => "_Thread_local",
+            _ => return None,
+        })
+    }
+
+    /// How the token is referred to in diagnostics.
+    pub fn describe(&self) -> String {
+        match self {
+            Token::Error => "invalid token".to_owned(),
+            Token::IntLiteral(val) => format!("integer literal `{}`", val),
+            Token::FloatLiteral(val) => format!("floating literal `{}`", val),
+            Token::Identifier(name) => format!("identifier `{}`", name),
+            Token::TypeName(name) => format!("type name `{}`", name),
+            Token::StringLiteral(val) => format!("string literal `{}`", val),
+            token if token.keyword().is_some() => format!("keyword `{}`", token),
+            token => format!("`{}`", token),
+        }
+    }
+}
+
+/// Why a stretch of input could not be lexed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum LexError {
+    /// A character that cannot start any token.
+    #[default]
+    UnexpectedCharacter,
+    /// A string literal not closed before the end of its line.
+    UnterminatedString,
+    /// A `/*` comment still open at the end of the input.
+    UnterminatedComment,
+}
+
+impl LexError {
+    /// Describes the error, given the `text` it was found at.
+    pub fn message(&self, text: &str) -> String {
+        match self {
+            LexError::UnexpectedCharacter => match text.chars().next() {
+                Some(c) if !c.is_ascii() => {
+                    format!("non-ASCII character `{}` (U+{:04X}) in source", c, c as u32)
+                }
+                Some(c) => format!("unexpected character `{}`", c.escape_debug()),
+                None => "unexpected end of input".to_owned(),
+            },
+            LexError::UnterminatedString => "missing terminating `\"` character".to_owned(),
+            LexError::UnterminatedComment => "unterminated `/*` comment".to_owned(),
+        }
+    }
+}
+
+fn unterminated_comment(_: &mut logos::Lexer<Token>) -> Result<(), LexError> {
+    Err(LexError::UnterminatedComment)
+}
+
+fn unterminated_string(_: &mut logos::Lexer<Token>) -> Result<String, LexError> {
+    Err(LexError::UnterminatedString)
 }
 
 impl std::fmt::Display for Token {
@@ -68,8 +283,11 @@ impl std::fmt::Display for Token {
             Token::RightParen => write!(f, ")"),
             Token::LeftBrace => write!(f, "{{"),
             Token::RightBrace => write!(f, "}}"),
+            Token::LeftBracket => write!(f, "["),
+            Token::RightBracket => write!(f, "]"),
             Token::Semicolon => write!(f, ";"),
             Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Asterisk => write!(f, "*"),
@@ -85,12 +303,16 @@ impl std::fmt::Display for Token {
             Token::Or => write!(f, "||"),
             Token::Assign => write!(f, "="),
             Token::Not => write!(f, "!"),
+            Token::Ampersand => write!(f, "&"),
+            Token::Dot => write!(f, "."),
+            Token::Arrow => write!(f, "->"),
+            Token::Ellipsis => write!(f, "..."),
             Token::IntLiteral(val) => write!(f, "IntLiteral({})", val),
+            Token::FloatLiteral(val) => write!(f, "FloatLiteral({})", val),
             Token::Identifier(name) => write!(f, "Identifier({})", name),
-            Token::If => write!(f, "if"),
-            Token::Else => write!(f, "else"),
-            Token::While => write!(f, "while"),
-            Token::Return => write!(f, "return"),
+            Token::TypeName(name) => write!(f, "TypeName({})", name),
+            Token::StringLiteral(val) => write!(f, "StringLiteral({})", val),
+            keyword => f.write_str(keyword.keyword().unwrap_or_default()),
         }
     }
 }
@@ -103,3 +325,47 @@ fn test_lexer() {
         println!("{:?}", token);
     }
 }
+
+#[test]
+fn test_float_literals() {
+    let input = "0.0 .5 3. 1.25 1e10 2.5e-3 4E+2 7";
+    let tokens = Token::lexer(input).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        tokens,
+        ["0.0", ".5", "3.", "1.25", "1e10", "2.5e-3", "4E+2"]
+            .into_iter()
+            .map(|s| Token::FloatLiteral(s.to_owned()))
+            .chain([Token::IntLiteral("7".to_owned())])
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_int_literals() {
+    let input = "0x1F 0XffU 017 0b101 42ul 7LL 9lu";
+    let tokens = Token::lexer(input).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        tokens,
+        input
+            .split(' ')
+            .map(|s| Token::IntLiteral(s.to_owned()))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_comments() {
+    let input = "a // line /* not a block\r\nb /* block\n * spanning // lines **/ c /**/ d";
+    let tokens = Token::lexer(input).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        tokens,
+        ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|s| Token::Identifier(s.to_owned()))
+            .collect::<Vec<_>>()
+    );
+
+    let mut lexer = Token::lexer("a / b /* never closed * /");
+    assert_eq!(lexer.nth(3), Some(Err(LexError::UnterminatedComment)));
+    assert_eq!(lexer.span(), 6..25);
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use super::token::Token;
+use chumsky::span::SimpleSpan;
+
+/// Where the declaration being read is: whether it is a typedef, whether it
+/// has a type specifier, which makes it a declaration, and whether the next
+/// identifier is the name it declares rather than a typedef name, as it is
+/// after the specifiers and after each `,` of the declarator list.
+#[derive(Clone, Copy, Default)]
+struct Declaration {
+    typedef: bool,
+    declarators: bool,
+    specified: bool,
+}
+
+impl Declaration {
+    /// A declaration whose type specifier has just been seen.
+    fn specify(&mut self) {
+        self.declarators = true;
+        self.specified = true;
+    }
+}
+
+/// A scope opened by `{` or `(`, with the names declared in it, each marked
+/// `true` if it is a typedef name, and the declaration to resume at its end.
+struct Scope {
+    kind: ScopeKind,
+    names: HashMap<String, bool>,
+    outer: Declaration,
+}
+
+/// What a scope is opened for, which decides what a `,` in it separates.
+#[derive(Clone, Copy, PartialEq)]
+enum ScopeKind {
+    /// A block, the body of a struct or union, or the file: the declarators
+    /// of one declaration.
+    Braces,
+    /// Parameters, each with specifiers of its own, or the arguments of a
+    /// call.
+    Parentheses,
+    /// The body of an enum: its enumerators, which are declared in the
+    /// enclosing scope.
+    Enumerators,
+}
+
+/// Where a `struct`, `union` or `enum` specifier is, as its tag and the `{`
+/// of its body are not part of the enclosing declaration.
+#[derive(Clone, Copy, PartialEq)]
+enum Tagged {
+    None,
+    Keyword,
+    Tag,
+}
+
+/// Turns every identifier that names a typedef in the scope it appears in
+/// into a `Token::TypeName`, which the parser takes as a type specifier.
+///
+/// C cannot be parsed without knowing which identifiers are typedef names:
+/// `a * b;` declares `b` if `a` is one, and multiplies otherwise. Scopes are
+/// tracked as C does, so that a variable or parameter declared with the name
+/// of a typedef hides it, e.g. in `typedef int n; void f(float n) { n * 2; }`.
+/// The parameters of a function definition are in the scope of its body.
+pub fn mark(tokens: &mut [(Token, SimpleSpan)]) {
+    let mut scopes = vec![Scope {
+        kind: ScopeKind::Braces,
+        names: HashMap::new(),
+        outer: Declaration::default(),
+    }];
+    let mut declaration = Declaration::default();
+    let mut tagged = Tagged::None;
+    // whether the last `struct`, `union` or `enum` keyword was `enum`
+    let mut enumeration = false;
+    // the parameters of the function declarator just closed
+    let mut parameters = None;
+    let mut member_access = false;
+
+    for (token, _) in tokens.iter_mut() {
+        let closed_parameters = parameters.take();
+        let after_member_access = std::mem::take(&mut member_access);
+        let after_tagged = std::mem::replace(&mut tagged, Tagged::None);
+        match token {
+            Token::Typedef => declaration.typedef = true,
+            Token::Void
+            | Token::Char
+            | Token::Short
+            | Token::Int
+            | Token::Long
+            | Token::Float
+            | Token::Double
+            | Token::Signed
+            | Token::Unsigned => declaration.specify(),
+            Token::Struct | Token::Union | Token::Enum => {
+                declaration.specify();
+                tagged = Tagged::Keyword;
+                enumeration = *token == Token::Enum;
+            }
+            // a tag, or the name of a member
+            Token::Identifier(_) if after_tagged == Tagged::Keyword => tagged = Tagged::Tag,
+            Token::Identifier(_) if after_member_access => {}
+            Token::Identifier(name) if declaration.specified => {
+                let depth = match scopes.last().map(|scope| scope.kind) {
+                    Some(ScopeKind::Enumerators) => scopes.len() - 2,
+                    _ => scopes.len() - 1,
+                };
+                scopes[depth]
+                    .names
+                    .insert(name.clone(), declaration.typedef);
+                declaration.specified = false;
+            }
+            Token::Identifier(name) => {
+                let typedef = scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.names.get(name.as_str()));
+                if typedef == Some(&true) {
+                    *token = Token::TypeName(std::mem::take(name));
+                    declaration.specify();
+                }
+            }
+            Token::Dot | Token::Arrow => member_access = true,
+            Token::LeftBrace => {
+                let scope = match after_tagged {
+                    // the body of a struct, union or enum, after which the
+                    // declaration goes on with its declarators
+                    Tagged::Keyword | Tagged::Tag => Scope {
+                        kind: if enumeration {
+                            ScopeKind::Enumerators
+                        } else {
+                            ScopeKind::Braces
+                        },
+                        names: HashMap::new(),
+                        outer: declaration,
+                    },
+                    Tagged::None => Scope {
+                        kind: ScopeKind::Braces,
+                        names: closed_parameters.unwrap_or_default(),
+                        outer: Declaration::default(),
+                    },
+                };
+                // an enum body starts with the name of an enumerator
+                declaration = Declaration {
+                    specified: scope.kind == ScopeKind::Enumerators,
+                    ..Declaration::default()
+                };
+                scopes.push(scope);
+            }
+            Token::LeftParen => {
+                scopes.push(Scope {
+                    kind: ScopeKind::Parentheses,
+                    names: HashMap::new(),
+                    outer: declaration,
+                });
+                declaration = Declaration::default();
+            }
+            // an unbalanced closer is reported by the parser
+            Token::RightBrace | Token::RightParen if scopes.len() > 1 => {
+                let scope = scopes.pop().expect("checked above");
+                declaration = scope.outer;
+                if *token == Token::RightParen {
+                    parameters = Some(scope.names);
+                }
+            }
+            Token::Semicolon => declaration = Declaration::default(),
+            Token::Comma => match scopes.last().map(|scope| scope.kind) {
+                Some(ScopeKind::Parentheses) => declaration = Declaration::default(),
+                Some(ScopeKind::Enumerators) => declaration.specified = true,
+                _ => declaration.specified = declaration.declarators,
+            },
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_mark() {
+    use super::lex;
+
+    let input = r#"
+        typedef int score;
+        typedef struct node { score value; struct node *next; } node;
+        score total(node *list, float score) { return score; }
+        int main() {
+          score * p;
+          { int score = 2; score * 3; }
+          node n;
+          n.score = 1;
+          return n.score;
+        }
+    "#;
+    let (mut tokens, diagnostics) = lex(input);
+    assert!(diagnostics.is_empty());
+    mark(&mut tokens);
+    let names: Vec<_> = tokens
+        .iter()
+        .filter(|(token, _)| match token {
+            Token::Identifier(name) | Token::TypeName(name) => name == "score" || name == "node",
+            _ => false,
+        })
+        .map(|(token, _)| token.to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            // the typedef declarations, with the struct tag in between
+            "Identifier(score)",
+            "Identifier(node)",
+            "TypeName(score)",
+            "Identifier(node)",
+            "Identifier(node)",
+            // `total`, whose parameter hides the typedef in its body
+            "TypeName(score)",
+            "TypeName(node)",
+            "Identifier(score)",
+            "Identifier(score)",
+            // `main`, with a block that hides it, and member names
+            "TypeName(score)",
+            "Identifier(score)",
+            "Identifier(score)",
+            "TypeName(node)",
+            "Identifier(score)",
+            "Identifier(score)",
+        ]
+    );
+}
+
+#[test]
+fn test_mark_shadowing() {
+    use super::lex;
+
+    let input = r#"
+        typedef int T;
+        typedef int A, B;
+        int f(int a, T b);
+        void g(float T) { T * 2; }
+        int main() {
+          B x;
+          { int a, T; T * 2; }
+          { enum { U, T = U + 1 }; T * 2; }
+          T y;
+          return (T) y;
+        }
+    "#;
+    let (mut tokens, diagnostics) = lex(input);
+    assert!(diagnostics.is_empty());
+    mark(&mut tokens);
+    let names: Vec<_> = tokens
+        .iter()
+        .filter(|(token, _)| match token {
+            Token::Identifier(name) | Token::TypeName(name) => {
+                matches!(name.as_str(), "T" | "A" | "B")
+            }
+            _ => false,
+        })
+        .map(|(token, _)| token.to_string())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            // every declarator of a typedef declares a typedef name
+            "Identifier(T)",
+            "Identifier(A)",
+            "Identifier(B)",
+            // each parameter has specifiers of its own
+            "TypeName(T)",
+            // a parameter hides the typedef in the function body
+            "Identifier(T)",
+            "Identifier(T)",
+            "TypeName(B)",
+            // so do a later declarator of a declaration and an enumerator
+            "Identifier(T)",
+            "Identifier(T)",
+            "Identifier(T)",
+            "Identifier(T)",
+            // until the end of the block
+            "TypeName(T)",
+            "TypeName(T)",
+        ]
+    );
+}
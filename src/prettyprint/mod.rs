@@ -1,4 +1,8 @@
-use crate::ast::{Ast, Block, BlockItem, Expression, Function, LValue, Literal, Statement, Type};
+use crate::ast::{
+    Ast, BinaryOperator, Block, BlockItem, Declaration, EnumDef, Expression, ExpressionKind,
+    Function, Item, LValue, LValueKind, Literal, Statement, StatementKind, StructDef, StructKind,
+    Type, Typedef, UnaryOperator,
+};
 
 trait PpWithCtx<C> {
     fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: C);
@@ -44,9 +48,17 @@ macro_rules! writeln_indent {
 impl PpWithCtx<PpCtx> for Ast {
     fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
         writeln_indent!(ctx, f, "Program:");
-        for func in &self.program {
-            func.pp(f, ctx.sub_ctx());
-            writeln_indent!(ctx, f);
+        for item in &self.program {
+            match item {
+                Item::Function(func) => {
+                    func.pp(f, ctx.sub_ctx());
+                    writeln_indent!(ctx, f);
+                }
+                Item::Global(decl) => decl.pp(f, ctx.sub_ctx()),
+                Item::Struct(definition) => definition.pp(f, ctx.sub_ctx()),
+                Item::Enum(definition) => definition.pp(f, ctx.sub_ctx()),
+                Item::Typedef(typedef) => typedef.pp(f, ctx.sub_ctx()),
+            }
         }
     }
 }
@@ -56,12 +68,25 @@ impl PpWithCtx<PpCtx> for Function {
         writeln_indent!(ctx, f, "Function: {}", self.name);
         write_indent!(ctx.sub_ctx(), f, "Args: ");
         for param in &self.params {
-            write!(f, "{} {}, ", &param.param_type, param.name).unwrap();
+            match &param.name {
+                Some(name) => write!(f, "{} {}, ", &param.param_type, name).unwrap(),
+                None => write!(f, "{}, ", &param.param_type).unwrap(),
+            }
+        }
+        if self.variadic {
+            write!(f, "..., ").unwrap();
         }
         writeln!(f).unwrap();
         writeln_indent!(ctx.sub_ctx(), f, "Return Type: {}", self.ret_type);
-        writeln_indent!(ctx.sub_ctx(), f, "Body: ");
-        self.body.pp(f, ctx.sub_ctx().sub_ctx());
+        match &self.body {
+            Some(body) => {
+                writeln_indent!(ctx.sub_ctx(), f, "Body: ");
+                body.pp(f, ctx.sub_ctx().sub_ctx());
+            }
+            None => {
+                writeln_indent!(ctx.sub_ctx(), f, "Prototype");
+            }
+        }
     }
 }
 
@@ -77,14 +102,10 @@ impl PpWithCtx<PpCtx> for Block {
 impl PpWithCtx<PpCtx> for BlockItem {
     fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
         match self {
-            BlockItem::Decl(decl) => {
-                writeln_indent!(ctx, f, "Declaration: {} {}", decl.var_type, decl.name);
-                if let Some(init) = &decl.init {
-                    write_indent!(ctx.sub_ctx(), f, "Init: ");
-                    init.pp(f, ctx.sub_ctx());
-                    writeln!(f).unwrap();
-                }
-            }
+            BlockItem::Decl(decl) => decl.pp(f, ctx),
+            BlockItem::Struct(definition) => definition.pp(f, ctx),
+            BlockItem::Enum(definition) => definition.pp(f, ctx),
+            BlockItem::Typedef(typedef) => typedef.pp(f, ctx),
             BlockItem::Stmt(stmt) => {
                 writeln_indent!(ctx, f, "Statement:");
                 stmt.pp(f, ctx.sub_ctx());
@@ -93,18 +114,70 @@ impl PpWithCtx<PpCtx> for BlockItem {
     }
 }
 
+impl PpWithCtx<PpCtx> for Declaration {
+    fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
+        writeln_indent!(ctx, f, "Declaration: {} {}", self.var_type, self.name);
+        if let Some(init) = &self.init {
+            write_indent!(ctx.sub_ctx(), f, "Init: ");
+            init.pp(f, ctx.sub_ctx());
+            writeln!(f).unwrap();
+        }
+    }
+}
+
+impl PpWithCtx<PpCtx> for StructDef {
+    fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
+        match self.kind {
+            StructKind::Struct => writeln_indent!(ctx, f, "Struct: {}", self.tag),
+            StructKind::Union => writeln_indent!(ctx, f, "Union: {}", self.tag),
+        }
+        for member in &self.members {
+            writeln_indent!(
+                ctx.sub_ctx(),
+                f,
+                "Member: {} {}",
+                member.member_type,
+                member.name
+            );
+        }
+    }
+}
+
+impl PpWithCtx<PpCtx> for EnumDef {
+    fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
+        match &self.tag {
+            Some(tag) => writeln_indent!(ctx, f, "Enum: {}", tag),
+            None => writeln_indent!(ctx, f, "Enum"),
+        }
+        for enumerator in &self.enumerators {
+            write_indent!(ctx.sub_ctx(), f, "Enumerator: {}", enumerator.name);
+            if let Some(value) = &enumerator.value {
+                write!(f, " = ").unwrap();
+                value.pp(f, ctx.sub_ctx());
+            }
+            writeln!(f).unwrap();
+        }
+    }
+}
+
+impl PpWithCtx<PpCtx> for Typedef {
+    fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
+        writeln_indent!(ctx, f, "Typedef: {} {}", self.ty, self.name);
+    }
+}
+
 impl PpWithCtx<PpCtx> for Statement {
     fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
-        match self {
-            Statement::Block(block) => {
+        match &self.kind {
+            StatementKind::Block(block) => {
                 block.pp(f, ctx);
             }
-            Statement::Expr(expr) => {
+            StatementKind::Expr(expr) => {
                 write_indent!(ctx, f, "Expr: ");
                 expr.pp(f, ctx);
                 writeln!(f).unwrap();
             }
-            Statement::Ret(expr_opt) => {
+            StatementKind::Ret(expr_opt) => {
                 write_indent!(ctx, f, "Return");
                 if let Some(expr) = expr_opt {
                     write!(f, " ");
@@ -112,7 +185,7 @@ impl PpWithCtx<PpCtx> for Statement {
                 }
                 writeln!(f).unwrap();
             }
-            Statement::If {
+            StatementKind::If {
                 condition,
                 then_branch,
                 else_branch,
@@ -128,7 +201,7 @@ impl PpWithCtx<PpCtx> for Statement {
                     else_branch.pp(f, ctx.sub_ctx().sub_ctx());
                 }
             }
-            Statement::While { condition, body } => {
+            StatementKind::While { condition, body } => {
                 writeln_indent!(ctx, f, "While:");
                 write_indent!(ctx.sub_ctx(), f, "Condition: ");
                 condition.pp(f, ctx.sub_ctx());
@@ -136,23 +209,47 @@ impl PpWithCtx<PpCtx> for Statement {
                 writeln_indent!(ctx.sub_ctx(), f, "Body:");
                 body.pp(f, ctx.sub_ctx().sub_ctx());
             }
-            Statement::Empty => {
+            StatementKind::Switch { condition, body } => {
+                writeln_indent!(ctx, f, "Switch:");
+                write_indent!(ctx.sub_ctx(), f, "Condition: ");
+                condition.pp(f, ctx.sub_ctx());
+                writeln!(f).unwrap();
+                writeln_indent!(ctx.sub_ctx(), f, "Body:");
+                body.pp(f, ctx.sub_ctx().sub_ctx());
+            }
+            StatementKind::Case { value, body } => {
+                write_indent!(ctx, f, "Case ");
+                value.pp(f, ctx);
+                writeln!(f, ":").unwrap();
+                body.pp(f, ctx.sub_ctx());
+            }
+            StatementKind::Default(body) => {
+                writeln_indent!(ctx, f, "Default:");
+                body.pp(f, ctx.sub_ctx());
+            }
+            StatementKind::Break => {
+                writeln_indent!(ctx, f, "Break;");
+            }
+            StatementKind::Empty => {
                 writeln_indent!(ctx, f, "Empty;");
             }
+            StatementKind::Error => {
+                writeln_indent!(ctx, f, "Error;");
+            }
         }
     }
 }
 
 impl PpWithCtx<PpCtx> for Expression {
     fn pp(&self, f: &mut std::fmt::Formatter<'_>, ctx: PpCtx) {
-        match self {
-            Expression::Literal(lit) => {
+        match &self.kind {
+            ExpressionKind::Literal(lit) => {
                 write!(f, "Literal({})", lit).unwrap();
             }
-            Expression::Variable(name) => {
+            ExpressionKind::Variable(name) => {
                 write!(f, "Variable({})", name).unwrap();
             }
-            Expression::Binary {
+            ExpressionKind::Binary {
                 left,
                 operator,
                 right,
@@ -163,19 +260,19 @@ impl PpWithCtx<PpCtx> for Expression {
                 right.pp(f, ctx);
                 write!(f, ")").unwrap();
             }
-            Expression::Unary { operator, operand } => {
+            ExpressionKind::Unary { operator, operand } => {
                 write!(f, "Unary({:?} ", operator).unwrap();
                 operand.pp(f, ctx);
                 write!(f, ")").unwrap();
             }
-            Expression::Assignment { target, value } => {
+            ExpressionKind::Assignment { target, value } => {
                 write!(f, "Assignment(").unwrap();
                 write!(f, "{}", target).unwrap();
                 write!(f, " = ").unwrap();
                 value.pp(f, ctx);
                 write!(f, ")").unwrap();
             }
-            Expression::FunctionCall { callee, arguments } => {
+            ExpressionKind::FunctionCall { callee, arguments } => {
                 write!(f, "FunctionCall(").unwrap();
                 callee.pp(f, ctx.clone());
                 write!(f, ", [").unwrap();
@@ -185,16 +282,83 @@ impl PpWithCtx<PpCtx> for Expression {
                 }
                 write!(f, "])").unwrap();
             }
+            ExpressionKind::Subscript { array, index } => {
+                write!(f, "Subscript(").unwrap();
+                array.pp(f, ctx.clone());
+                write!(f, ", ").unwrap();
+                index.pp(f, ctx);
+                write!(f, ")").unwrap();
+            }
+            ExpressionKind::Member { object, member } => {
+                write!(f, "Member(").unwrap();
+                object.pp(f, ctx);
+                write!(f, ", {})", member).unwrap();
+            }
+            ExpressionKind::Cast { ty, operand } => {
+                write!(f, "Cast({}, ", ty).unwrap();
+                operand.pp(f, ctx);
+                write!(f, ")").unwrap();
+            }
+            ExpressionKind::Error => {
+                write!(f, "Error").unwrap();
+            }
+        }
+    }
+}
+
+/// Splits a type name into its specifiers and abstract declarator, the way
+/// C spells it: `int *[4]` is an array of pointers, `int (*)[4]` a pointer to
+/// an array.
+fn declarator(ty: &Type, inner: String) -> (&Type, String) {
+    match ty {
+        Type::Pointer(pointee) if matches!(**pointee, Type::Array(..)) => {
+            declarator(pointee, format!("(*{})", inner))
         }
+        Type::Pointer(pointee) => declarator(pointee, format!("*{}", inner)),
+        Type::Array(element, len) => declarator(element, format!("{}[{}]", inner, len)),
+        ty => (ty, inner),
+    }
+}
+
+/// The tag of a struct, union or enum type as written, without the number
+/// code generation adds to tell apart types given the same tag in different
+/// scopes, as in `tmp.1`.
+fn source_tag(tag: &str) -> &str {
+    tag.split('.').next().unwrap_or(tag)
+}
+
+/// Writes a type named by `tag` as `keyword tag`, or as `anonymous struct`
+/// for one defined without a tag, which the parser names after its offset,
+/// as in `(anonymous at 8)`, where no identifier could clash with it.
+fn write_tagged(f: &mut std::fmt::Formatter<'_>, keyword: &str, tag: &str) -> std::fmt::Result {
+    if tag.starts_with('(') {
+        write!(f, "anonymous {}", keyword)
+    } else {
+        write!(f, "{} {}", keyword, source_tag(tag))
     }
 }
 
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Type::Char => write!(f, "char"),
+            Type::UnsignedChar => write!(f, "unsigned char"),
             Type::Int => write!(f, "int"),
+            Type::UnsignedInt => write!(f, "unsigned int"),
+            Type::Long => write!(f, "long"),
+            Type::UnsignedLong => write!(f, "unsigned long"),
+            Type::LongLong => write!(f, "long long"),
+            Type::UnsignedLongLong => write!(f, "unsigned long long"),
             Type::Float => write!(f, "float"),
             Type::Void => write!(f, "void"),
+            Type::Struct(tag) => write_tagged(f, "struct", tag),
+            Type::Union(tag) => write_tagged(f, "union", tag),
+            Type::Enum(tag) => write_tagged(f, "enum", tag),
+            Type::Typedef(name) => write!(f, "{}", name),
+            Type::Pointer(_) | Type::Array(..) => {
+                let (base, declarator) = declarator(self, String::new());
+                write!(f, "{} {}", base, declarator)
+            }
         }
     }
 }
@@ -202,7 +366,11 @@ impl std::fmt::Display for Type {
 impl std::fmt::Display for Literal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Literal::Int(n) => write!(f, "Int({})", n),
+            Literal::Int {
+                value,
+                ty: Type::Int,
+            } => write!(f, "Int({})", value),
+            Literal::Int { value, ty } => write!(f, "Int({}: {})", value, ty),
             Literal::Float(n) => write!(f, "Float({})", n),
         }
     }
@@ -210,12 +378,63 @@ impl std::fmt::Display for Literal {
 
 impl std::fmt::Display for LValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            LValue::Var(name) => write!(f, "Var({})", name),
+        match &self.kind {
+            LValueKind::Var(name) => write!(f, "Var({})", name),
+            LValueKind::Deref(pointer) => {
+                write!(f, "Deref(")?;
+                pointer.pp(f, PpCtx { indent: 0 });
+                write!(f, ")")
+            }
+            LValueKind::Subscript { array, index } => {
+                write!(f, "Subscript(")?;
+                array.pp(f, PpCtx { indent: 0 });
+                write!(f, ", ")?;
+                index.pp(f, PpCtx { indent: 0 });
+                write!(f, ")")
+            }
+            LValueKind::Member { object, member } => {
+                write!(f, "Member(")?;
+                object.pp(f, PpCtx { indent: 0 });
+                write!(f, ", {})", member)
+            }
         }
     }
 }
 
+impl std::fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let spelling = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulus => "%",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        };
+        f.write_str(spelling)
+    }
+}
+
+impl std::fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let spelling = match self {
+            UnaryOperator::Plus => "+",
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+            UnaryOperator::Deref => "*",
+            UnaryOperator::AddressOf => "&",
+        };
+        f.write_str(spelling)
+    }
+}
+
 impl std::fmt::Display for Ast {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ctx = PpCtx { indent: 0 };